@@ -0,0 +1,61 @@
+//! Compares the allocations made by case insensitive search with and without a compiled query
+//!
+//! Run with `cargo bench --bench compiled_query`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use grep_remake::{search_case_insensitive, CompiledQuery};
+
+/// Number of allocations made so far by the whole program
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocator that counts allocations, passing them on to the system allocator
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Runs a search, printing how many allocations and how long it took
+fn measure(name: &str, search: impl FnOnce() -> usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let found = search();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!("{:<28} {:>8} allocations {:>10.2?} ({} lines found)", name, allocations, elapsed, found);
+}
+
+fn main() {
+    let contents: String = (0..100_000)
+        .map(|index| if index % 10 == 0 { "Some Text With The NEEDLE in it\n" } else { "Some plain text of the usual length\n" })
+        .collect();
+
+    measure("lowercase every line", || {
+        let query = "needle".to_lowercase();
+        contents.lines().filter(|line| line.to_lowercase().contains(&query)).count()
+    });
+    measure("search_case_insensitive", || search_case_insensitive("needle", &contents).len());
+    measure("CompiledQuery::matches_line", || {
+        let query = CompiledQuery::new("needle");
+        contents.lines().filter(|line| query.matches_line(line)).count()
+    });
+}
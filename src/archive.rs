@@ -0,0 +1,470 @@
+//! Searching inside tar archives without extracting them
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+
+use crate::{display_path, search_stream, BinaryMode, Config, Source};
+
+/// Size of a tar header and of the blocks member contents are padded to
+const BLOCK_SIZE: usize = 512;
+
+/// Is archive method
+///
+/// Checks whether a path names an archive this module can read
+///
+/// # Parameters
+///
+/// `filename` - Path of the search file
+///
+/// # Returns
+///
+/// `bool` - True for `.tar`, `.tar.gz` and `.tgz` files
+pub fn is_archive(filename: &str) -> bool {
+    filename.ends_with(".tar") || is_compressed(filename)
+}
+
+/// Is compressed method
+///
+/// # Parameters
+///
+/// `filename` - Path of the search file
+///
+/// # Returns
+///
+/// `bool` - True when the archive is gzip compressed
+fn is_compressed(filename: &str) -> bool {
+    filename.ends_with(".tar.gz") || filename.ends_with(".tgz")
+}
+
+/// Search archive method
+///
+/// Searches every regular file inside an archive. Members are streamed straight out of
+/// the archive, so only their matching lines are held in memory. A member that can't be
+/// searched as text, being binary or not UTF-8, is skipped with a warning (or silently with
+/// `--binary-files=without-match`) and the rest of the archive is still searched.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `filename` - Path of the archive
+/// `display` - Name the archive is shown with in the output
+///
+/// # Returns
+///
+/// `io::Result<(Vec<Source>, Vec<String>)>` - Matching lines of each member, named
+/// `archive::member`, and a warning for each member skipped
+pub fn search_archive(config: &Config, filename: &str, display: &str) -> io::Result<(Vec<Source>, Vec<String>)> {
+    let mut results = Vec::new();
+    let mut warnings = Vec::new();
+
+    let mut search_member = |member: &str, contents: &mut dyn Read| -> io::Result<()> {
+        let name = format!("{}::{}", display, display_path(config, member));
+        let mut reader = BufReader::new(contents);
+
+        // Like a plain file, a NUL byte in the first block read marks the member as binary
+        if config.binary_mode != BinaryMode::Text && reader.fill_buf()?.contains(&0) {
+            if config.binary_mode != BinaryMode::WithoutMatch {
+                warnings.push(format!("grep_remake: {}: binary member skipped", name));
+            }
+            return Ok(());
+        }
+
+        match search_stream(config, reader) {
+            Ok(scan) => results.push((Some(name), scan)),
+            // Text the search can't read only skips its member, the archive itself is fine
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => warnings.push(format!("grep_remake: {}: {}", name, e)),
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    };
+
+    if is_compressed(filename) {
+        // Decompress through gzip so the tar never has to be written out
+        let mut child = gunzip(filename)?;
+        let mut stdout = child.stdout.take().expect("gzip stdout is piped");
+
+        let walked = for_each_member(&mut stdout, &mut search_member);
+        // The padding after the end of the tar is read too, or gzip is killed writing it
+        let _ = io::copy(&mut stdout, &mut io::sink());
+        drop(stdout);
+        let status = child.wait()?;
+
+        // A decompression failure explains a truncated tar better than the tar error does
+        if !status.success() {
+            return Err(corrupt("gzip could not decompress it"));
+        }
+        walked.map_err(describe)?;
+    } else {
+        for_each_member(File::open(filename)?, &mut search_member).map_err(describe)?;
+    }
+
+    Ok((results, warnings))
+}
+
+/// Gunzip method
+///
+/// # Parameters
+///
+/// `filename` - Path of the compressed archive
+///
+/// # Returns
+///
+/// `io::Result<Child>` - Running `gzip -dc` with the decompressed archive on its stdout
+fn gunzip(filename: &str) -> io::Result<Child> {
+    Command::new("gzip")
+        .arg("-dc")
+        .arg(filename)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/// For each member method
+///
+/// Walks the headers of a tar stream, handing the contents of each regular file to
+/// `visit`. Directories, links and other special entries are skipped.
+///
+/// # Parameters
+///
+/// `reader` - The tar stream
+/// `visit` - Called with the member path and a reader limited to the member contents
+///
+/// # Returns
+///
+/// `io::Result<()>` - `InvalidData` when the archive is malformed
+fn for_each_member<R: Read>(
+    mut reader: R,
+    visit: &mut dyn FnMut(&str, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut header = [0u8; BLOCK_SIZE];
+    // Path given by a preceding GNU long name entry
+    let mut long_name: Option<String> = None;
+
+    loop {
+        // An archive must end with zero blocks, so running out mid-way means it is truncated
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("archive ends unexpectedly"),
+            _ => e,
+        })?;
+
+        // The first zero block marks the end of the archive
+        if header.iter().all(|&byte| byte == 0) {
+            return Ok(());
+        }
+
+        if !checksum_matches(&header) {
+            return Err(invalid("header checksum mismatch"));
+        }
+
+        let size = parse_size(&header[124..136])?;
+        let name = long_name.take().unwrap_or_else(|| header_path(&header));
+        let mut contents = (&mut reader).take(size);
+
+        match header[156] {
+            // Regular files
+            b'0' | 0 => visit(&name, &mut contents)?,
+            // GNU long name, the contents are the path of the next entry
+            b'L' => {
+                let mut bytes = Vec::new();
+                contents.read_to_end(&mut bytes)?;
+                long_name = Some(c_string(&bytes));
+            }
+            _ => {}
+        }
+
+        // Skip whatever the visitor did not read, then the block padding
+        io::copy(&mut contents, &mut io::sink())?;
+        if contents.limit() > 0 {
+            return Err(invalid("archive ends unexpectedly"));
+        }
+        let padding = (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+        io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+    }
+}
+
+/// Checksum matches method
+///
+/// # Parameters
+///
+/// `header` - A tar header block
+///
+/// # Returns
+///
+/// `bool` - True when the stored checksum agrees with the header bytes
+fn checksum_matches(header: &[u8; BLOCK_SIZE]) -> bool {
+    // The checksum is taken with its own field filled with spaces
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(index, &byte)| if (148..156).contains(&index) { u64::from(b' ') } else { u64::from(byte) })
+        .sum();
+
+    parse_octal(&header[148..156]) == Some(sum)
+}
+
+/// Parse size method
+///
+/// Reads a size field, which is octal text or, for large members, big-endian base 256
+///
+/// # Parameters
+///
+/// `field` - The bytes of the field
+///
+/// # Returns
+///
+/// `io::Result<u64>` - The size in bytes
+fn parse_size(field: &[u8]) -> io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..].iter().fold(0, |size, &byte| (size << 8) | u64::from(byte)));
+    }
+
+    parse_octal(field).ok_or_else(|| invalid("bad member size"))
+}
+
+/// Parse octal method
+///
+/// # Parameters
+///
+/// `field` - Octal digits, padded with spaces or NULs
+///
+/// # Returns
+///
+/// `Option<u64>` - The value, or None if the field holds anything else
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let digits = text.trim_matches(|c| c == ' ' || c == '\0');
+
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// Header path method
+///
+/// # Parameters
+///
+/// `header` - A tar header block
+///
+/// # Returns
+///
+/// `String` - The member path, joining the ustar prefix field when present
+fn header_path(header: &[u8; BLOCK_SIZE]) -> String {
+    let name = c_string(&header[0..100]);
+
+    if &header[257..262] == b"ustar" {
+        let prefix = c_string(&header[345..500]);
+        if !prefix.is_empty() {
+            return format!("{}/{}", prefix, name);
+        }
+    }
+
+    name
+}
+
+/// C string method
+///
+/// # Parameters
+///
+/// `bytes` - A NUL terminated (or NUL padded) field
+///
+/// # Returns
+///
+/// `String` - The text before the first NUL
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Invalid method
+///
+/// # Parameters
+///
+/// `reason` - What is wrong with the archive
+///
+/// # Returns
+///
+/// `io::Error` - An `InvalidData` error
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+/// Corrupt method
+///
+/// # Parameters
+///
+/// `reason` - What is wrong with the archive
+///
+/// # Returns
+///
+/// `io::Error` - An error saying the archive is corrupt, which the caller names it in
+fn corrupt(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt tar archive ({})", reason))
+}
+
+/// Describe method
+///
+/// Marks structural errors as archive corruption, leaving other IO errors as they are
+///
+/// # Parameters
+///
+/// `error` - Error from walking the archive
+///
+/// # Returns
+///
+/// `io::Error` - The error to report
+fn describe(error: io::Error) -> io::Error {
+    match error.kind() {
+        io::ErrorKind::InvalidData => corrupt(&error.to_string()),
+        _ => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scan;
+
+    /// Builds a tar header for a member
+    fn header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+        let mut block = vec![0u8; BLOCK_SIZE];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        block[156] = kind;
+        block[257..263].copy_from_slice(b"ustar\0");
+
+        // Checksum is computed with the field blank
+        block[148..156].copy_from_slice(b"        ");
+        let sum: u32 = block.iter().map(|&byte| u32::from(byte)).sum();
+        block[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        block
+    }
+
+    /// Builds a tar entry, its header then its contents padded to a whole block
+    fn entry(name: &str, kind: u8, contents: &[u8]) -> Vec<u8> {
+        let mut bytes = header(name, contents.len(), kind);
+        bytes.extend(contents);
+        bytes.resize(bytes.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        bytes
+    }
+
+    /// Builds a tar archive from (name, type, contents) entries
+    fn archive(entries: &[(&str, u8, &str)]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = entries.iter().flat_map(|(name, kind, contents)| entry(name, *kind, contents.as_bytes())).collect();
+        bytes.extend(vec![0u8; BLOCK_SIZE * 2]);
+        bytes
+    }
+
+    /// Member walk test
+    ///
+    /// Test if regular members are visited with their contents and other entries skipped
+    #[test]
+    fn walks_regular_members() {
+        let bytes = archive(&[
+            ("logs/", b'5', ""),
+            ("logs/a.log", b'0', "first\nerror here\n"),
+            ("logs/link", b'2', ""),
+            ("b.log", b'0', "error again"),
+        ]);
+
+        let mut seen = Vec::new();
+        for_each_member(&bytes[..], &mut |name, contents| {
+            let mut text = String::new();
+            contents.read_to_string(&mut text)?;
+            seen.push((name.to_string(), text));
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(
+            vec![
+                ("logs/a.log".to_string(), "first\nerror here\n".to_string()),
+                ("b.log".to_string(), "error again".to_string()),
+            ],
+            seen
+        );
+    }
+
+    /// Archive search test
+    ///
+    /// Test if matches inside an archive are named with the archive and member paths
+    #[test]
+    fn searches_archive_members() {
+        let path = std::env::temp_dir().join(format!("grep_remake_archive_{}.tar", std::process::id()));
+        std::fs::write(&path, archive(&[("logs/a.log", b'0', "ok\nerror here\n")])).unwrap();
+        let filename = path.to_string_lossy().into_owned();
+
+        let args: Vec<String> = vec!["grep_remake".into(), "--archives".into(), "error".into(), filename.clone()];
+        let config = Config::new(&args).unwrap();
+
+        assert_eq!(
+            vec![(
+                Some(format!("{}::logs/a.log", filename)),
+                Scan { matches: vec![(2, "error here".to_string())], denominator: 0, context: Vec::new(), bytes_read: 14, unterminated_line: None, sections: Vec::new(), timed_out: false }
+            )],
+            search_archive(&config, &filename, &filename).unwrap().0
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Skipped member test
+    ///
+    /// Test if binary and non UTF-8 members are skipped with a warning while the others are still searched
+    #[test]
+    fn skips_unreadable_members() {
+        let path = std::env::temp_dir().join(format!("grep_remake_archive_skip_{}.tar", std::process::id()));
+        let mut bytes = entry("m/bin.dat", b'0', b"\xff\xfe");
+        bytes.extend(entry("m/nul.dat", b'0', b"error\0here"));
+        bytes.extend(entry("m/a.log", b'0', b"error here\n"));
+        bytes.extend(vec![0u8; BLOCK_SIZE * 2]);
+        std::fs::write(&path, bytes).unwrap();
+        let filename = path.to_string_lossy().into_owned();
+
+        let args: Vec<String> = vec!["grep_remake".into(), "--archives".into(), "error".into(), filename.clone()];
+        let (sources, warnings) = search_archive(&Config::new(&args).unwrap(), &filename, "a.tar").unwrap();
+        assert_eq!(vec![Some("a.tar::m/a.log".to_string())], sources.into_iter().map(|(name, _)| name).collect::<Vec<_>>());
+        assert_eq!(2, warnings.len());
+        assert!(warnings[0].starts_with("grep_remake: a.tar::m/bin.dat: "), "{}", warnings[0]);
+        assert_eq!("grep_remake: a.tar::m/nul.dat: binary member skipped", warnings[1]);
+
+        // Without a match binary members are left out quietly
+        let args: Vec<String> = vec!["grep_remake".into(), "--archives".into(), "--binary-files".into(), "without-match".into(), "error".into(), filename.clone()];
+        assert_eq!(1, search_archive(&Config::new(&args).unwrap(), &filename, "a.tar").unwrap().1.len());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Compressed archive test
+    ///
+    /// Test if a gzipped archive with a long run of padding after its end is searched without gzip failing
+    #[test]
+    fn searches_padded_gzip_archives() {
+        let tar = std::env::temp_dir().join(format!("grep_remake_archive_padded_{}.tar", std::process::id()));
+        let mut bytes = archive(&[("a.log", b'0', "error here\n")]);
+        // More zeros than a pipe holds, so gzip would block writing them if they weren't read
+        bytes.extend(vec![0u8; 1 << 20]);
+        std::fs::write(&tar, bytes).unwrap();
+        assert!(Command::new("gzip").arg("-f").arg(&tar).status().unwrap().success());
+        let filename = format!("{}.gz", tar.to_string_lossy());
+
+        let args: Vec<String> = vec!["grep_remake".into(), "--archives".into(), "error".into(), filename.clone()];
+        let (sources, warnings) = search_archive(&Config::new(&args).unwrap(), &filename, "a.tar.gz").unwrap();
+        assert_eq!(1, sources.len());
+        assert!(warnings.is_empty());
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    /// Corrupt archive test
+    ///
+    /// Test if damaged or truncated archives give an InvalidData error rather than a panic
+    #[test]
+    fn corrupt_archives() {
+        let mut bytes = archive(&[("a.log", b'0', "error")]);
+        bytes[0] = b'z';
+        let error = for_each_member(&bytes[..], &mut |_, _| Ok(())).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        let bytes = archive(&[("a.log", b'0', "error")]);
+        let error = for_each_member(&bytes[..600], &mut |_, _| Ok(())).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+}
@@ -0,0 +1,147 @@
+//! Finding the balanced brackets around a match, for printing only the bracketed expression
+//! it is in (`--balance`)
+
+use std::ops::Range;
+
+/// Brackets used when `--balance-brackets` doesn't give others
+pub const DEFAULT_BRACKETS: &str = "()[]{}";
+
+/// Parse pairs method
+///
+/// # Parameters
+///
+/// `brackets` - Opening and closing brackets one after the other, such as `()[]`
+///
+/// # Returns
+///
+/// `Option<Vec<(char, char)>>` - Each opening bracket with its closing one, or None unless the
+/// text is pairs of two different characters
+pub fn parse_pairs(brackets: &str) -> Option<Vec<(char, char)>> {
+    let chars: Vec<char> = brackets.chars().collect();
+    if chars.is_empty() || !chars.len().is_multiple_of(2) {
+        return None;
+    }
+    chars.chunks(2).map(|pair| (pair[0] != pair[1]).then_some((pair[0], pair[1]))).collect()
+}
+
+/// Enclosing method
+///
+/// Walks outward from a match to the nearest pair of brackets that holds all of it, counting
+/// nesting so that pairs opened and closed beside the match are passed over
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+/// `found` - Byte range of the match
+/// `pairs` - Opening and closing brackets
+///
+/// # Returns
+///
+/// `Option<Range<usize>>` - Byte range of the brackets and everything between them, or None
+/// when the match isn't inside a balanced pair
+pub fn enclosing(line: &str, found: &Range<usize>, pairs: &[(char, char)]) -> Option<Range<usize>> {
+    let mut before = found.start;
+    loop {
+        let (open, close) = unclosed_opening(&line[..before], pairs)?;
+        let end = matching_close(line, open, close)?;
+        // A pair that closes inside the match doesn't hold it, so look further out
+        if end >= found.end {
+            return Some(open..end);
+        }
+        before = open;
+    }
+}
+
+/// Unclosed opening method
+///
+/// # Parameters
+///
+/// `text` - The text before a point in the line
+/// `pairs` - Opening and closing brackets
+///
+/// # Returns
+///
+/// `Option<(usize, char)>` - Byte offset of the last bracket opened and not closed before the
+/// point, with the bracket that closes it
+fn unclosed_opening(text: &str, pairs: &[(char, char)]) -> Option<(usize, char)> {
+    // Brackets closed before the point, waiting for their opening one
+    let mut closed = Vec::new();
+    for (index, c) in text.char_indices().rev() {
+        if pairs.iter().any(|&(_, close)| close == c) {
+            closed.push(c);
+        } else if let Some(&(_, close)) = pairs.iter().find(|&&(open, _)| open == c) {
+            match closed.last() {
+                Some(&last) if last == close => {
+                    closed.pop();
+                }
+                None => return Some((index, close)),
+                // A mismatched pair isn't balanced, so it is passed over like any other text
+                Some(_) => {}
+            }
+        }
+    }
+    None
+}
+
+/// Matching close method
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+/// `open` - Byte offset of an opening bracket
+/// `close` - The bracket that closes it
+///
+/// # Returns
+///
+/// `Option<usize>` - Byte offset just past the bracket closing it, or None if it is never closed
+fn matching_close(line: &str, open: usize, close: char) -> Option<usize> {
+    let opening = line[open..].chars().next()?;
+    let mut depth = 0;
+    for (index, c) in line[open..].char_indices() {
+        if c == opening {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(open + index + c.len_utf8());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Enclosing test
+    ///
+    /// Test if a match is extended to the nearest brackets holding it, nested pairs beside it passed over
+    #[test]
+    fn finds_enclosing_brackets() {
+        let pairs = parse_pairs(DEFAULT_BRACKETS).unwrap();
+        let line = "let x = call(a, [b, c], inner(needle, d[0]), e);";
+        let needle = line.find("needle").unwrap();
+        let span = enclosing(line, &(needle..needle + 6), &pairs).unwrap();
+        assert_eq!("(needle, d[0])", &line[span]);
+
+        // The argument list of `call`, as the match runs out of the square brackets it starts in
+        let start = line.find("c]").unwrap();
+        let span = enclosing(line, &(start..start + 9), &pairs).unwrap();
+        assert_eq!("(a, [b, c], inner(needle, d[0]), e)", &line[span]);
+
+        assert_eq!(None, enclosing("no brackets here", &(3..11), &pairs));
+        assert_eq!(None, enclosing("never (closed", &(7..13), &pairs));
+    }
+
+    /// Parse pairs test
+    ///
+    /// Test if a bracket set is read as pairs of different characters
+    #[test]
+    fn parses_pairs() {
+        assert_eq!(Some(vec![('(', ')'), ('<', '>')]), parse_pairs("()<>"));
+        assert_eq!(None, parse_pairs("(()"));
+        assert_eq!(None, parse_pairs("\"\""));
+        assert_eq!(None, parse_pairs(""));
+    }
+}
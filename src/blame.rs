@@ -0,0 +1,111 @@
+//! Annotating matches with the commit that last changed them, from `git blame`
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Length of the abbreviated commit hashes printed
+const SHORT_HASH: usize = 8;
+
+/// Blame method
+///
+/// Runs `git blame --porcelain` on a file once, so every match in it can be annotated
+/// without another subprocess
+///
+/// # Parameters
+///
+/// `filename` - Path of the file
+///
+/// # Returns
+///
+/// `Option<HashMap<usize, String>>` - The `(author, commit)` annotation of each line by its
+/// number, or None if git isn't installed or the file isn't tracked by it
+pub fn blame(filename: &str) -> Option<HashMap<usize, String>> {
+    let path = Path::new(filename);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+
+    // Run from the file's directory so the right repository is found
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["blame", "--porcelain", "--"])
+        .arg(path.file_name()?)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+    Some(parse_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse porcelain method
+///
+/// Each line of the file is described by a `<hash> <original line> <final line>` header,
+/// followed the first time a commit appears by its details (such as `author <name>`), then
+/// the line itself after a tab
+///
+/// # Parameters
+///
+/// `porcelain` - Output of `git blame --porcelain`
+///
+/// # Returns
+///
+/// `HashMap<usize, String>` - The `(author, commit)` annotation of each line by its number
+pub fn parse_porcelain(porcelain: &str) -> HashMap<usize, String> {
+    let mut authors: HashMap<&str, &str> = HashMap::new();
+    let mut annotations = HashMap::new();
+    // Commit and final line number of the header being read
+    let mut current: Option<(&str, usize)> = None;
+
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            // The line's contents end its entry, by which point its commit's author is known
+            if let Some((hash, number)) = current.take() {
+                let author = authors.get(hash).copied().unwrap_or("unknown");
+                annotations.insert(number, format!("({}, {})", author, &hash[..SHORT_HASH.min(hash.len())]));
+            }
+        } else if let Some(author) = line.strip_prefix("author ") {
+            if let Some((hash, _)) = current {
+                authors.insert(hash, author);
+            }
+        } else {
+            let mut words = line.split(' ');
+            let hash = words.next().unwrap_or_default();
+            let is_header = hash.len() == 40 && hash.bytes().all(|byte| byte.is_ascii_hexdigit());
+            if let (true, Some(number)) = (is_header, words.nth(1).and_then(|word| word.parse().ok())) {
+                current = Some((hash, number));
+            }
+        }
+    }
+
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Porcelain parse test
+    ///
+    /// Test if lines from a commit seen earlier still get its author, as its details are only given once
+    #[test]
+    fn parses_porcelain() {
+        let porcelain = "\
+ce0858351051b1356cbb87350037c49b30037b11 1 1 2
+author Ada L
+author-mail <a@x>
+summary init
+filename f.txt
+\ta
+ce0858351051b1356cbb87350037c49b30037b11 2 2
+\tb match
+";
+        let annotations = parse_porcelain(porcelain);
+        assert_eq!(Some(&"(Ada L, ce085835)".to_string()), annotations.get(&1));
+        assert_eq!(Some(&"(Ada L, ce085835)".to_string()), annotations.get(&2));
+        assert_eq!(2, annotations.len());
+    }
+}
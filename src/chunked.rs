@@ -0,0 +1,199 @@
+//! Searching one large file on several threads, split into chunks of whole lines (`--chunk-size`)
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::{search_stream, Config, Scan};
+
+/// Line counter struct
+///
+/// Counts the newlines read through it, so a chunk knows how many lines it held
+///
+/// # Arguments
+///
+/// * `inner` - The reader counted
+/// * `newlines` - Newlines read so far
+struct LineCounter<R> {
+    inner: R,
+    newlines: usize,
+}
+
+impl<R: Read> Read for LineCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.newlines += buf[..read].iter().filter(|&&byte| byte == b'\n').count();
+        Ok(read)
+    }
+}
+
+/// Chunkable method
+///
+/// Chunks are searched without knowing what came before them, so options that carry
+/// anything from one line to the next rule chunking out, as does a time limit for the whole file
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+///
+/// # Returns
+///
+/// `bool` - True when searching the chunks separately gives the same result as searching the file
+pub fn chunkable(config: &Config) -> bool {
+    config.head_lines.is_none()
+        && config.tail_lines.is_none()
+        && config.range_start.is_none()
+        && config.range_end.is_none()
+        && config.before_context == 0
+        && config.after_context == 0
+        && !config.paragraph_mode
+        && config.section_pattern.is_none()
+        && !config.first_match_per_file
+        && config.per_file_timeout.is_none()
+}
+
+/// Chunk bounds method
+///
+/// Splits the bytes of a file into chunks of about `chunk_size`, each one extended to the
+/// end of the line it would have stopped inside so no line is split between two
+///
+/// # Parameters
+///
+/// `file` - The file
+/// `bytes` - Range of bytes to split, from where the search starts to the end of the file
+/// `chunk_size` - How many bytes a chunk should have
+///
+/// # Returns
+///
+/// `io::Result<Vec<Range<u64>>>` - The chunks in order, covering every byte of the range
+pub fn chunk_bounds(file: &mut File, bytes: Range<u64>, chunk_size: u64) -> io::Result<Vec<Range<u64>>> {
+    let mut chunks = Vec::new();
+    let mut start = bytes.start;
+
+    while bytes.end - start > chunk_size {
+        // The chunk ends after the first newline at or past its last byte
+        let last = start + chunk_size.max(1) - 1;
+        file.seek(SeekFrom::Start(last))?;
+        let mut rest_of_line = Vec::new();
+        let read = BufReader::new(&mut *file).take(bytes.end - last).read_until(b'\n', &mut rest_of_line)?;
+
+        let end = last + read as u64;
+        if end >= bytes.end {
+            break;
+        }
+        chunks.push(start..end);
+        start = end;
+    }
+
+    chunks.push(start..bytes.end);
+    Ok(chunks)
+}
+
+/// Search chunked method
+///
+/// Searches the chunks of a file on `workers` threads, then puts their results back together
+/// in file order with line numbers counted from the start of the file
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `filename` - Path of the file
+/// `start` - Byte to start searching from, for `--resume-from`
+/// `chunk_size` - How many bytes a chunk should have
+/// `workers` - Number of threads to search on
+///
+/// # Returns
+///
+/// `io::Result<Scan>` - The same scan a search of the whole file would give
+pub fn search_chunked(config: &Config, filename: &str, start: u64, chunk_size: u64, workers: usize) -> io::Result<Scan> {
+    let mut file = File::open(filename)?;
+    let length = file.metadata()?.len();
+    let chunks = chunk_bounds(&mut file, start..length, chunk_size)?;
+
+    // Index of the next chunk a worker should pick up
+    let next_chunk = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    let mut results: Vec<Option<(Scan, usize)>> = (0..chunks.len()).map(|_| None).collect();
+    thread::scope(|scope| -> io::Result<()> {
+        for _ in 0..workers.min(chunks.len()) {
+            let sender = sender.clone();
+            let (next_chunk, chunks) = (&next_chunk, &chunks);
+
+            scope.spawn(move || loop {
+                let index = next_chunk.fetch_add(1, Ordering::SeqCst);
+                let chunk = match chunks.get(index) {
+                    Some(chunk) => chunk.clone(),
+                    None => break,
+                };
+
+                let result = File::open(filename).and_then(|mut file| {
+                    file.seek(SeekFrom::Start(chunk.start))?;
+                    let mut reader = BufReader::new(LineCounter { inner: file.take(chunk.end - chunk.start), newlines: 0 });
+                    let scan = search_stream(config, &mut reader)?;
+                    Ok((scan, reader.into_inner().newlines))
+                });
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // Only the workers hold senders now, so the loop below ends once they finish
+        drop(sender);
+        for (index, result) in receiver {
+            results[index] = Some(result?);
+        }
+        Ok(())
+    })?;
+
+    // Each chunk counted its lines from 1, so the lines of the chunks before it are added on
+    let mut merged = Scan::default();
+    let mut lines_before = 0;
+    for (mut scan, newlines) in results.into_iter().flatten() {
+        for (number, _) in scan.matches.iter_mut().chain(scan.context.iter_mut()).chain(scan.sections.iter_mut()) {
+            *number += lines_before;
+        }
+        merged.matches.append(&mut scan.matches);
+        merged.context.append(&mut scan.context);
+        merged.sections.append(&mut scan.sections);
+        merged.denominator += scan.denominator;
+        merged.bytes_read += scan.bytes_read;
+        // Only the last chunk can end without a newline
+        merged.unterminated_line = scan.unterminated_line.map(|number| number + lines_before);
+        lines_before += newlines;
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Chunk bounds test
+    ///
+    /// Test if every chunk but the last ends just after a newline, and together they cover the file
+    #[test]
+    fn chunks_end_at_newlines() {
+        let path = env::temp_dir().join(format!("grep_remake_chunks_{}.txt", std::process::id()));
+        let contents = "short\na much longer line than the chunk size\nx\n\nend without newline";
+        fs::write(&path, contents).unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let chunks = chunk_bounds(&mut file, 0..contents.len() as u64, 4).unwrap();
+        assert_eq!(0, chunks[0].start);
+        assert_eq!(contents.len() as u64, chunks.last().unwrap().end);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_eq!(b'\n', contents.as_bytes()[pair[0].end as usize - 1]);
+        }
+        // A long line makes its chunk long, and one ending inside the last line runs to the end
+        assert_eq!(vec![0..6, 6..45, 45..contents.len() as u64], chunks);
+        fs::remove_file(&path).unwrap();
+    }
+}
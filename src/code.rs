@@ -0,0 +1,123 @@
+//! Telling comments and strings apart from the rest of a line of source code, for
+//! `--in-comments` and `--in-strings`
+//!
+//! This is a heuristic for C-style languages (C, C++, Java, JavaScript, Rust, Go and the
+//! like) that looks at one line at a time rather than parsing the file:
+//!
+//! * `//` starts a comment running to the end of the line
+//! * `/*` starts one running to the next `*/`, or the end of the line
+//! * A line starting with `*`, as the inside of a block comment usually does, is a comment
+//! * `"` starts a string running to the next `"` that isn't escaped with a backslash
+//!
+//! Block comments and strings spanning lines are only recognised on their first line, and
+//! single quotes are ignored so lifetimes and apostrophes aren't taken for strings.
+
+use std::ops::Range;
+
+/// Region enum
+///
+/// # Variants
+///
+/// * `Comment` - A comment, its markers included
+/// * `String` - A string literal, its quotes included
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Region {
+    Comment,
+    String,
+}
+
+/// Regions method
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+///
+/// # Returns
+///
+/// `Vec<(Range<usize>, Region)>` - Byte ranges of the comments and strings in the line, in order
+pub fn regions(line: &str) -> Vec<(Range<usize>, Region)> {
+    let bytes = line.as_bytes();
+    if line.trim_start().starts_with('*') {
+        return vec![(0..line.len(), Region::Comment)];
+    }
+
+    let mut regions = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let next = bytes.get(index + 1).copied();
+        match (bytes[index], next) {
+            (b'/', Some(b'/')) => {
+                regions.push((index..bytes.len(), Region::Comment));
+                break;
+            }
+            (b'/', Some(b'*')) => {
+                let end = line[index + 2..].find("*/").map_or(bytes.len(), |found| index + 2 + found + 2);
+                regions.push((index..end, Region::Comment));
+                index = end;
+            }
+            (b'"', _) => {
+                let mut end = index + 1;
+                while end < bytes.len() && bytes[end] != b'"' {
+                    // An escaped character, such as \", can't end the string
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                let end = (end + 1).min(bytes.len());
+                regions.push((index..end, Region::String));
+                index = end;
+            }
+            _ => index += 1,
+        }
+    }
+    regions
+}
+
+/// Within method
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+/// `matches` - Byte ranges of the matches in the line
+/// `wanted` - The kinds of region a match may be in
+///
+/// # Returns
+///
+/// `Vec<Range<usize>>` - The matches that lie wholly inside a region of a wanted kind
+pub fn within(line: &str, matches: Vec<Range<usize>>, wanted: &[Region]) -> Vec<Range<usize>> {
+    let regions = regions(line);
+    matches
+        .into_iter()
+        .filter(|found| {
+            regions
+                .iter()
+                .any(|(region, kind)| wanted.contains(kind) && region.start <= found.start && found.end <= region.end)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Region test
+    ///
+    /// Test if line and block comments and strings with escaped quotes are found in C-style lines
+    #[test]
+    fn finds_regions() {
+        assert_eq!(vec![(7..19, Region::Comment)], regions("int x; // TODO: fix"));
+        assert_eq!(vec![(0..10, Region::Comment), (15..20, Region::String)], regions("/* note */ s = \"a//\";"));
+        assert_eq!(vec![(4..15, Region::String), (16..20, Region::Comment)], regions("f = \"say \\\"x\\\"\" // y"));
+        assert_eq!(vec![(0..14, Region::Comment)], regions("   * continued"));
+        assert_eq!(vec![(8..15, Region::Comment)], regions("int y = /* open"));
+        assert!(regions("return a / b;").is_empty());
+    }
+
+    /// Match region test
+    ///
+    /// Test if a match counts only when it lies inside a region of a kind asked for
+    #[test]
+    fn filters_matches() {
+        let line = "todo(); // todo later";
+        assert_eq!(vec![11..15], within(line, vec![0..4, 11..15], &[Region::Comment]));
+        assert!(within(line, vec![0..4, 11..15], &[Region::String]).is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! Searching compressed files through an external decompressor
+
+use std::io::{self, BufReader};
+use std::process::{Command, Stdio};
+
+use crate::{search_stream, Config, Scan};
+
+/// Decompressor struct
+///
+/// A program that writes the decompressed contents of a file to its stdout
+///
+/// # Arguments
+///
+/// * `extension` - File name ending of the files it reads, including the dot
+/// * `program` - The command run
+/// * `args` - Arguments given before the path
+pub struct Decompressor {
+    pub extension: &'static str,
+    pub program: &'static str,
+    pub args: &'static [&'static str],
+}
+
+/// Every supported format, a new one only needing an entry here
+pub const DECOMPRESSORS: &[Decompressor] = &[
+    Decompressor { extension: ".gz", program: "gzip", args: &["-dc"] },
+    Decompressor { extension: ".bz2", program: "bzip2", args: &["-dc"] },
+    Decompressor { extension: ".xz", program: "xz", args: &["-dc"] },
+];
+
+/// Decompressor for method
+///
+/// # Parameters
+///
+/// `filename` - Path of the search file
+///
+/// # Returns
+///
+/// `Option<&Decompressor>` - The decompressor for its extension, or None for a file read as it is
+pub fn decompressor_for(filename: &str) -> Option<&'static Decompressor> {
+    DECOMPRESSORS.iter().find(|decompressor| filename.ends_with(decompressor.extension))
+}
+
+/// Search compressed method
+///
+/// Searches the decompressed contents of a file as they are written, so they are never
+/// held in memory or on disk
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `decompressor` - The decompressor for the file
+/// `filename` - Path of the compressed file
+///
+/// # Returns
+///
+/// `io::Result<Scan>` - Matches in the decompressed text, or an error if the decompressor
+/// could not be run or could not decompress the file
+pub fn search_compressed(config: &Config, decompressor: &Decompressor, filename: &str) -> io::Result<Scan> {
+    let mut child = Command::new(decompressor.program)
+        .args(decompressor.args)
+        .arg(filename)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("decompressor stdout is piped");
+
+    let scan = search_stream(config, BufReader::new(stdout));
+    let status = child.wait()?;
+
+    // A decompression failure explains a truncated stream better than the search error does
+    if !status.success() {
+        return Err(io::Error::other(format!("{} could not decompress it", decompressor.program)));
+    }
+    scan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompressor lookup test
+    ///
+    /// Test if each registered extension finds its program and other files are read as they are
+    #[test]
+    fn finds_decompressor_by_extension() {
+        assert_eq!(Some("gzip"), decompressor_for("logs/app.log.gz").map(|d| d.program));
+        assert_eq!(Some("bzip2"), decompressor_for("app.log.bz2").map(|d| d.program));
+        assert_eq!(Some("xz"), decompressor_for("app.log.xz").map(|d| d.program));
+        assert!(decompressor_for("app.log.zst").is_none());
+        assert!(decompressor_for("app.log").is_none());
+    }
+}
@@ -0,0 +1,63 @@
+//! The error type returned by the public functions
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Grep error enum
+///
+/// Why a search couldn't be set up or carried out
+///
+/// # Variants
+///
+/// * `Io` - A file or stream couldn't be read or written, with the path of the file when
+///   there is one, such as a saved pattern that is missing
+/// * `InvalidPattern` - The pattern given can't be searched for, such as an empty one
+/// * `Config` - The arguments couldn't be parsed, such as an unknown option
+#[derive(Debug)]
+pub enum GrepError {
+    Io { path: Option<String>, error: io::Error },
+    InvalidPattern(String),
+    Config(String),
+}
+
+impl fmt::Display for GrepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrepError::Io { path: Some(path), error } => write!(f, "{}: {}", path, error),
+            GrepError::Io { path: None, error } => write!(f, "{}", error),
+            GrepError::InvalidPattern(message) | GrepError::Config(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for GrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GrepError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl GrepError {
+    /// At method
+    ///
+    /// # Parameters
+    ///
+    /// `path` - The file the error happened on
+    /// `error` - What went wrong reading or writing it
+    ///
+    /// # Returns
+    ///
+    /// `GrepError` - An `Io` error naming the file
+    pub fn at(path: &str, error: io::Error) -> GrepError {
+        GrepError::Io { path: Some(path.to_string()), error }
+    }
+}
+
+impl From<io::Error> for GrepError {
+    fn from(error: io::Error) -> GrepError {
+        GrepError::Io { path: None, error }
+    }
+}
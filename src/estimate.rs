@@ -0,0 +1,113 @@
+//! Estimating how many matches a search would find from a random sample of its files (`--estimate`)
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Split mix struct
+///
+/// The SplitMix64 generator, which is small and good enough for picking files, and gives the
+/// same numbers everywhere for the same seed
+///
+/// # Arguments
+///
+/// * `state` - Advanced by a fixed amount for each number
+struct SplitMix(u64);
+
+impl SplitMix {
+    /// Next method
+    ///
+    /// # Returns
+    ///
+    /// `u64` - The next number
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Clock seed method
+///
+/// # Returns
+///
+/// `u64` - A seed that differs from run to run, for when `--seed` isn't given
+pub fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// Sample method
+///
+/// Picks `percent` of the files, rounded up so there is always at least one to search
+///
+/// # Parameters
+///
+/// `count` - Number of files to pick from
+/// `percent` - How much of them to pick, from 1 to 100
+/// `seed` - Seed of the picks, the same one always picking the same files
+///
+/// # Returns
+///
+/// `Vec<usize>` - Indexes of the files picked, in order
+pub fn sample(count: usize, percent: usize, seed: u64) -> Vec<usize> {
+    let size = (count * percent).div_ceil(100).min(count);
+    let mut rng = SplitMix(seed);
+
+    // The first `size` places of a Fisher-Yates shuffle, which is all that is needed
+    let mut indexes: Vec<usize> = (0..count).collect();
+    for place in 0..size {
+        let swap = place + (rng.next() % (count - place) as u64) as usize;
+        indexes.swap(place, swap);
+    }
+    indexes.truncate(size);
+    indexes.sort_unstable();
+    indexes
+}
+
+/// Extrapolate method
+///
+/// # Parameters
+///
+/// `matches` - Matches found in the sample
+/// `sampled` - Number of files searched
+/// `total` - Number of files there are
+///
+/// # Returns
+///
+/// `usize` - The matches all the files would have, were they like the sample, to the nearest one
+pub fn extrapolate(matches: usize, sampled: usize, total: usize) -> usize {
+    if sampled == 0 {
+        return 0;
+    }
+    (matches * total + sampled / 2) / sampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sample test
+    ///
+    /// Test if a seed always picks the same files, and another seed picks others
+    #[test]
+    fn seeded_sample() {
+        assert_eq!(vec![2, 6, 8, 13, 16], sample(20, 25, 42));
+        assert_eq!(sample(20, 25, 42), sample(20, 25, 42));
+        assert_ne!(sample(20, 25, 42), sample(20, 25, 7));
+
+        // At least one file is searched, and all of them at 100%
+        assert_eq!(1, sample(3, 1, 42).len());
+        assert_eq!((0..3).collect::<Vec<_>>(), sample(3, 100, 42));
+        assert!(sample(0, 50, 42).is_empty());
+    }
+
+    /// Extrapolate test
+    ///
+    /// Test if the sample's matches are scaled up to every file and rounded
+    #[test]
+    fn extrapolated_total() {
+        assert_eq!(120, extrapolate(12, 10, 100));
+        assert_eq!(3, extrapolate(2, 3, 5));
+        assert_eq!(0, extrapolate(0, 0, 10));
+    }
+}
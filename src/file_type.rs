@@ -0,0 +1,89 @@
+//! Named sets of file extensions, for choosing files by language with `--type` and `--type-not`
+
+use std::path::Path;
+
+/// File types known by name, with the extensions of their files
+const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["c", "h"]),
+    ("cpp", &["cpp", "cc", "cxx", "hpp", "hh", "hxx", "h"]),
+    ("go", &["go"]),
+    ("java", &["java"]),
+    ("js", &["js", "mjs", "cjs"]),
+    ("json", &["json"]),
+    ("md", &["md", "markdown"]),
+    ("py", &["py", "pyi"]),
+    ("rust", &["rs"]),
+    ("sh", &["sh", "bash"]),
+    ("toml", &["toml"]),
+    ("ts", &["ts", "tsx"]),
+    ("txt", &["txt"]),
+    ("web", &["html", "htm", "css", "js"]),
+    ("yaml", &["yaml", "yml"]),
+];
+
+/// Extensions method
+///
+/// # Parameters
+///
+/// `name` - Name of the file type, such as `rust`
+///
+/// # Returns
+///
+/// `Option<&[&str]>` - Extensions of the type's files without their dots, or None if there is
+/// no type by that name
+pub fn extensions(name: &str) -> Option<&'static [&'static str]> {
+    FILE_TYPES.iter().find(|(known, _)| *known == name).map(|(_, extensions)| *extensions)
+}
+
+/// Known method
+///
+/// # Returns
+///
+/// `String` - The names of every file type, separated by commas for an error message
+pub fn known() -> String {
+    FILE_TYPES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ")
+}
+
+/// Selected method
+///
+/// # Parameters
+///
+/// `types` - Types a file must be one of, or empty to allow any (`--type`)
+/// `types_not` - Types a file mustn't be (`--type-not`)
+/// `path` - Path of the file
+///
+/// # Returns
+///
+/// `bool` - True when the file should be searched. Unknown type names match nothing.
+pub fn selected(types: &[String], types_not: &[String], path: &str) -> bool {
+    let extension = Path::new(path).extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    let is_type = |name: &String| match (&extension, extensions(name)) {
+        (Some(extension), Some(known)) => known.contains(&extension.as_str()),
+        _ => false,
+    };
+
+    (types.is_empty() || types.iter().any(is_type)) && !types_not.iter().any(is_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Selected test
+    ///
+    /// Test if a file is chosen by the extensions of the types asked for, and of the types ruled out
+    #[test]
+    fn selects_by_type() {
+        let rust = vec!["rust".to_string()];
+        assert!(selected(&rust, &[], "src/lib.rs"));
+        assert!(selected(&rust, &[], "src/MAIN.RS"));
+        assert!(!selected(&rust, &[], "README.md"));
+        assert!(!selected(&rust, &[], "Makefile"));
+
+        let web = vec!["web".to_string()];
+        assert!(selected(&[], &web, "src/lib.rs"));
+        assert!(!selected(&[], &web, "site/style.css"));
+        assert!(selected(&[], &[], "Makefile"));
+        assert_eq!(None, extensions("cobol"));
+    }
+}
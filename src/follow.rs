@@ -0,0 +1,185 @@
+//! Watching a file that is still being written and printing new matching lines as they are
+//! added, like `tail -f | grep` (`--follow`)
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::Duration;
+
+use crate::{format_line, search_stream, Config, LineKind};
+
+/// How long to wait before looking for new data again
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Bytes of whole lines searched together, so a big file isn't read into memory all at once
+const BATCH_SIZE: usize = 64 * 1024;
+
+/// Follow method
+///
+/// Searches a file from the start (or `--resume-from`), then keeps it open, searching
+/// whatever is added after the end. Only whole lines are searched, so a line that is
+/// half-written is left until its newline arrives. A file that shrinks, as a log does when
+/// it is truncated, is searched again from the start. Only the matching lines are printed,
+/// which is why `Config::validate` refuses the options that print anything else.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `filename` - Path of the file
+/// `name` - Name to prefix the lines with, if any
+/// `out` - Where the lines are written, flushed after each batch so they show straight away
+/// `stopped` - Checked between polls, and following ends once it is true, as on Ctrl-C
+///
+/// # Returns
+///
+/// `io::Result<usize>` - Number of matching lines printed, or an error reading the file
+pub fn follow(config: &Config, filename: &str, name: Option<&str>, out: &mut dyn Write, stopped: &dyn Fn() -> bool) -> io::Result<usize> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    // Bytes read so far, the line after them still being written if it is in `partial`
+    let mut offset = config.resume_from.unwrap_or(0);
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut partial = Vec::new();
+    // Lines before the batch, so numbers count from the start of the file
+    let mut lines_before = config.line_base;
+    let mut printed = 0;
+
+    while !stopped() {
+        if reader.get_ref().metadata()?.len() < offset {
+            offset = 0;
+            lines_before = 0;
+            partial.clear();
+            reader.seek(SeekFrom::Start(0))?;
+        }
+
+        // Whole lines added since the last poll, searched a batch at a time
+        let mut batch = Vec::new();
+        loop {
+            let read = reader.read_until(b'\n', &mut partial)?;
+            offset += read as u64;
+            if !partial.ends_with(b"\n") {
+                break;
+            }
+            batch.append(&mut partial);
+            if batch.len() >= BATCH_SIZE {
+                printed += print_batch(config, name, &batch, &mut lines_before, out)?;
+                batch.clear();
+            }
+        }
+        printed += print_batch(config, name, &batch, &mut lines_before, out)?;
+        out.flush()?;
+
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(printed)
+}
+
+/// Print batch method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `name` - Name to prefix the lines with, if any
+/// `batch` - Whole lines read from the file
+/// `lines_before` - Number of lines in the file before the batch, moved past it
+/// `out` - Where the matching lines are written
+///
+/// # Returns
+///
+/// `io::Result<usize>` - Number of matching lines printed
+fn print_batch(config: &Config, name: Option<&str>, batch: &[u8], lines_before: &mut usize, out: &mut dyn Write) -> io::Result<usize> {
+    if batch.is_empty() {
+        return Ok(0);
+    }
+
+    let numbered = Config { line_base: *lines_before, ..config.clone() };
+    let matches = search_stream(&numbered, batch)?.matches;
+    for (line_number, line) in &matches {
+        writeln!(out, "{}", format_line(config, name, *line_number, line.clone(), LineKind::Match, None))?;
+    }
+    *lines_before += batch.iter().filter(|&&byte| byte == b'\n').count();
+    Ok(matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, OpenOptions};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    /// Shared buffer writer
+    ///
+    /// Lets the test read what the following thread has written so far
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Follow test
+    ///
+    /// Test if lines appended to a followed file are printed once complete, numbered from the start of the file
+    #[test]
+    fn prints_appended_matches() {
+        let path = env::temp_dir().join(format!("grep_remake_follow_{}.log", std::process::id()));
+        fs::write(&path, "old match\nskip\n").unwrap();
+        let filename = path.to_string_lossy().to_string();
+
+        let args: Vec<String> = ["grep_remake", "-n", "--follow", "match", &filename].iter().map(|arg| arg.to_string()).collect();
+        let config = Config::new(&args).unwrap();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let follower = {
+            let (written, stop, filename) = (Arc::clone(&written), Arc::clone(&stop), filename.clone());
+            thread::spawn(move || follow(&config, &filename, None, &mut Shared(written), &|| stop.load(Ordering::SeqCst)))
+        };
+
+        // Waits, for at most a few seconds, until the output is as expected
+        let wait_for = |expected: &str| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while String::from_utf8_lossy(&written.lock().unwrap()) != expected && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(20));
+            }
+            assert_eq!(expected, String::from_utf8_lossy(&written.lock().unwrap()));
+        };
+        wait_for("1:old match\n");
+
+        // The half-written line is only printed once the rest of it arrives
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        log.write_all(b"new match\nhalf a mat").unwrap();
+        wait_for("1:old match\n3:new match\n");
+        log.write_all(b"ch\n").unwrap();
+        wait_for("1:old match\n3:new match\n4:half a match\n");
+
+        stop.store(true, Ordering::SeqCst);
+        assert_eq!(3, follower.join().unwrap().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Follow options test
+    ///
+    /// Test if the options printing something other than matching lines are refused with --follow
+    #[test]
+    fn refuses_other_output() {
+        let refused = |option: &[&str]| {
+            let mut args = vec!["grep_remake", "--follow"];
+            args.extend_from_slice(option);
+            args.extend_from_slice(&["match", "app.log"]);
+            Config::new(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>()).is_err()
+        };
+        assert!(refused(&["-c"]));
+        assert!(refused(&["-o"]));
+        assert!(refused(&["-C", "2"]));
+        assert!(refused(&["--format", "json"]));
+        assert!(!refused(&["-n"]));
+    }
+}
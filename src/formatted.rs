@@ -0,0 +1,189 @@
+//! Printing a matched or context line with its filename and line number prefixes
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::ContextStyle;
+
+/// Colour of filenames, as grep prints them (magenta)
+const PATH_COLOR: &str = "\x1b[35m";
+/// Colour of line numbers (green)
+const LINE_NUMBER_COLOR: &str = "\x1b[32m";
+/// Colour of matched text (bold red)
+const MATCH_COLOR: &str = "\x1b[1;31m";
+/// Matched lines with `ContextStyle::Dim` (bold)
+const BOLD: &str = "\x1b[1m";
+/// Context lines with `ContextStyle::Dim` (faint)
+const FAINT: &str = "\x1b[2m";
+/// Turns every colour back off
+const RESET: &str = "\x1b[0m";
+
+/// Formatted match struct
+///
+/// A line ready to print, `Display` writing it as `path:line:text` with whichever parts are
+/// turned on
+///
+/// # Arguments
+///
+/// * `path` - Name of the file the line is in
+/// * `line_number` - Position of the line in its file, starting at 1
+/// * `text` - Text of the line as it is printed
+/// * `highlights` - Byte ranges of `text` coloured as matches when `color` is on
+/// * `context` - The line is context around a match, so its prefixes end with `-` rather than `:`
+/// * `show_filename` - Start with the path
+/// * `show_line_number` - Follow the path with the line number
+/// * `null` - End the path with a NUL rather than a separator, for `-Z`
+/// * `color` - Colour the path, line number and highlights with ANSI codes
+/// * `context_style` - How the text of matched and context lines differ when `color` is on
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FormattedMatch<'a> {
+    pub path: &'a str,
+    pub line_number: usize,
+    pub text: &'a str,
+    pub highlights: Vec<Range<usize>>,
+    pub context: bool,
+    pub show_filename: bool,
+    pub show_line_number: bool,
+    pub null: bool,
+    pub color: bool,
+    pub context_style: ContextStyle,
+}
+
+impl FormattedMatch<'_> {
+    /// Paint method
+    ///
+    /// # Parameters
+    ///
+    /// `f` - Where the text is written
+    /// `color` - ANSI code to start the text with
+    /// `text` - The text
+    ///
+    /// # Returns
+    ///
+    /// `fmt::Result` - Simple error flag
+    fn paint(&self, f: &mut fmt::Formatter<'_>, color: &str, text: &dyn fmt::Display) -> fmt::Result {
+        if self.color {
+            write!(f, "{}{}{}", color, text, RESET)
+        } else {
+            write!(f, "{}", text)
+        }
+    }
+}
+
+impl fmt::Display for FormattedMatch<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Like grep, context lines use dashes where matches use colons
+        let separator = if self.context { '-' } else { ':' };
+
+        if self.show_filename {
+            self.paint(f, PATH_COLOR, &self.path)?;
+            // With -Z the filename is followed by a NUL rather than a separator
+            write!(f, "{}", if self.null { '\0' } else { separator })?;
+        }
+
+        if self.show_line_number {
+            self.paint(f, LINE_NUMBER_COLOR, &self.line_number)?;
+            write!(f, "{}", separator)?;
+        }
+
+        if !self.color {
+            return f.write_str(self.text);
+        }
+
+        // Dimmed context makes the rest of a matched line bold, and the line style has to be
+        // started again after each highlight resets it
+        let style = match (self.context_style, self.context) {
+            (ContextStyle::Plain, _) => "",
+            (ContextStyle::Dim, false) => BOLD,
+            (ContextStyle::Dim, true) => FAINT,
+        };
+
+        // Text between the highlights is written in the line style
+        f.write_str(style)?;
+        let mut written = 0;
+        for range in &self.highlights {
+            f.write_str(&self.text[written..range.start])?;
+            self.paint(f, MATCH_COLOR, &&self.text[range.clone()])?;
+            f.write_str(style)?;
+            written = range.end;
+        }
+        f.write_str(&self.text[written..])?;
+
+        if style.is_empty() {
+            Ok(())
+        } else {
+            f.write_str(RESET)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plain display test
+    ///
+    /// Test if the filename and line number prefixes appear only when turned on
+    #[test]
+    fn displays_prefixes() {
+        let formatted = FormattedMatch { path: "src/a.rs", line_number: 7, text: "let x = 1;", ..FormattedMatch::default() };
+        assert_eq!("let x = 1;", formatted.to_string());
+
+        let formatted = FormattedMatch { show_filename: true, show_line_number: true, ..formatted };
+        assert_eq!("src/a.rs:7:let x = 1;", formatted.to_string());
+
+        let formatted = FormattedMatch { show_filename: false, context: true, ..formatted };
+        assert_eq!("7-let x = 1;", formatted.to_string());
+
+        let formatted = FormattedMatch { show_filename: true, show_line_number: false, null: true, ..formatted };
+        assert_eq!("src/a.rs\0let x = 1;", formatted.to_string());
+    }
+
+    /// Colour display test
+    ///
+    /// Test if colour wraps the path, line number and each highlight in its own codes
+    #[test]
+    fn displays_colors() {
+        let formatted = FormattedMatch {
+            path: "a.rs",
+            line_number: 3,
+            text: "x = x + 1",
+            highlights: vec![0..1, 4..5],
+            show_filename: true,
+            show_line_number: true,
+            color: true,
+            ..FormattedMatch::default()
+        };
+
+        assert_eq!(
+            "\x1b[35ma.rs\x1b[0m:\x1b[32m3\x1b[0m:\x1b[1;31mx\x1b[0m = \x1b[1;31mx\x1b[0m + 1",
+            formatted.to_string()
+        );
+    }
+
+    /// Context style test
+    ///
+    /// Test if dimmed context makes context lines faint and matched lines bold, and does nothing without colour
+    #[test]
+    fn displays_context_style() {
+        let context = FormattedMatch {
+            path: "a.rs",
+            line_number: 2,
+            text: "fn main() {",
+            context: true,
+            color: true,
+            context_style: ContextStyle::Dim,
+            ..FormattedMatch::default()
+        };
+        assert_eq!("\x1b[2mfn main() {\x1b[0m", context.to_string());
+
+        let matched = FormattedMatch { text: "todo!(todo)", highlights: vec![0..4, 6..10], context: false, ..context.clone() };
+        assert_eq!(
+            "\x1b[1m\x1b[1;31mtodo\x1b[0m\x1b[1m!(\x1b[1;31mtodo\x1b[0m\x1b[1m)\x1b[0m",
+            matched.to_string()
+        );
+
+        let plain = FormattedMatch { color: false, ..context };
+        assert_eq!("fn main() {", plain.to_string());
+    }
+}
@@ -0,0 +1,203 @@
+//! Shell style glob patterns for matching file paths
+
+/// Class struct
+///
+/// A parsed `[...]` character set
+///
+/// # Arguments
+///
+/// * `members` - Ranges of characters in the set, a single character being a range of one
+/// * `negated` - The set was written `[!...]` and matches characters not in it
+/// * `length` - Characters the set takes up in the pattern after the `[`, including the `]`
+struct Class {
+    members: Vec<(char, char)>,
+    negated: bool,
+    length: usize,
+}
+
+impl Class {
+    /// Contains method
+    ///
+    /// # Parameters
+    ///
+    /// `c` - The character to look for
+    ///
+    /// # Returns
+    ///
+    /// `bool` - True when the set matches the character
+    fn contains(&self, c: char) -> bool {
+        self.members.iter().any(|&(start, end)| start <= c && c <= end) != self.negated
+    }
+}
+
+/// Matches path method
+///
+/// A pattern without a `/` is matched against the file name alone, so `*.toml` matches
+/// `config/app.toml`. A pattern with one is matched against the whole path.
+///
+/// # Parameters
+///
+/// `pattern` - The glob
+/// `path` - Path of the file
+///
+/// # Returns
+///
+/// `bool` - True when the path matches the glob
+pub fn matches_path(pattern: &str, path: &str) -> bool {
+    let target = if pattern.contains('/') { path } else { path.rsplit('/').next().unwrap_or(path) };
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let target: Vec<char> = target.chars().collect();
+    matches(&pattern, &target)
+}
+
+/// Excluded method
+///
+/// Rules are globs that exclude the paths they match, or with a leading `!` include them
+/// again. Like gitignore, they are applied in order and the last one matching a path decides.
+///
+/// # Parameters
+///
+/// `rules` - The globs, in the order given
+/// `path` - Path of the file
+///
+/// # Returns
+///
+/// `bool` - True when the path is excluded
+pub fn excluded(rules: &[String], path: &str) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find_map(|rule| match rule.strip_prefix('!') {
+            Some(glob) => matches_path(glob, path).then_some(false),
+            None => matches_path(rule, path).then_some(true),
+        })
+        .unwrap_or(false)
+}
+
+/// Matches method
+///
+/// `*` matches any run of characters within one path component, `**` any run across them,
+/// `?` any single character other than `/` and `[...]` one character from a set such as
+/// `[abc]`, `[a-z]` or `[!0-9]`
+///
+/// # Parameters
+///
+/// `pattern` - Characters of the glob
+/// `text` - Characters to match against
+///
+/// # Returns
+///
+/// `bool` - True when the whole of `text` matches
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => {
+            // "**" may cross directory separators, a single star may not
+            let (rest, crosses) = match pattern.get(1) {
+                Some('*') => (&pattern[2..], true),
+                _ => (&pattern[1..], false),
+            };
+
+            // Try every split point, stopping at a separator for a single star
+            for skip in 0..=text.len() {
+                if matches(rest, &text[skip..]) {
+                    return true;
+                }
+                if !crosses && text.get(skip) == Some(&'/') {
+                    return false;
+                }
+            }
+            false
+        }
+        Some('?') => text.first().is_some_and(|&c| c != '/') && matches(&pattern[1..], &text[1..]),
+        Some('[') => match (text.first(), class(&pattern[1..])) {
+            (Some(&c), Some(class)) => c != '/' && class.contains(c) && matches(&pattern[1 + class.length..], &text[1..]),
+            // An unclosed bracket is an ordinary character
+            (Some('['), None) => matches(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some(&c) => text.first() == Some(&c) && matches(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Class method
+///
+/// # Parameters
+///
+/// `pattern` - The glob after an opening `[`
+///
+/// # Returns
+///
+/// `Option<Class>` - The set, or None if it is never closed
+fn class(pattern: &[char]) -> Option<Class> {
+    let negated = pattern.first() == Some(&'!');
+    let mut index = if negated { 1 } else { 0 };
+    let mut members = Vec::new();
+
+    // A "]" straight after the opening bracket is part of the set
+    while let Some(&c) = pattern.get(index) {
+        if c == ']' && index > usize::from(negated) {
+            return Some(Class { members, negated, length: index + 1 });
+        }
+
+        match (pattern.get(index + 1), pattern.get(index + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                members.push((c, end));
+                index += 3;
+            }
+            _ => {
+                members.push((c, c));
+                index += 1;
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wildcard test
+    ///
+    /// Test if stars stay within a path component unless doubled
+    #[test]
+    fn wildcards() {
+        assert!(matches_path("*.toml", "config/app.toml"));
+        assert!(!matches_path("*.toml", "config/app.tomlx"));
+        assert!(matches_path("config/*.ini", "config/app.ini"));
+        assert!(!matches_path("config/*.ini", "config/nested/app.ini"));
+        assert!(matches_path("config/**.ini", "config/nested/app.ini"));
+        assert!(matches_path("app.?ni", "app.ini"));
+    }
+
+    /// Character class test
+    ///
+    /// Test if sets, ranges and negated sets match single characters
+    #[test]
+    fn character_classes() {
+        assert!(matches_path("log[0-9].txt", "log7.txt"));
+        assert!(!matches_path("log[!0-9].txt", "log7.txt"));
+        assert!(matches_path("[ab]*.cfg", "b.cfg"));
+        assert!(matches_path("[]x].cfg", "].cfg"));
+        assert!(matches_path("odd[name", "odd[name"));
+    }
+
+    /// Rule order test
+    ///
+    /// Test if a later negated rule includes paths excluded before it, and a later rule can exclude them again
+    #[test]
+    fn ordered_rules() {
+        let rules: Vec<String> = ["vendor/**", "!vendor/keep/**", "*.bak"].iter().map(|rule| rule.to_string()).collect();
+        assert!(excluded(&rules, "vendor/lib/a.rs"));
+        assert!(!excluded(&rules, "vendor/keep/a.rs"));
+        assert!(excluded(&rules, "vendor/keep/a.rs.bak"));
+        assert!(!excluded(&rules, "src/main.rs"));
+
+        // An include before the exclude it was meant to undo is overridden by it
+        let rules: Vec<String> = ["!vendor/keep/**", "vendor/**"].iter().map(|rule| rule.to_string()).collect();
+        assert!(excluded(&rules, "vendor/keep/a.rs"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Rewriting files with their matches replaced, for `--in-place`
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{replace_matches, search_stream, signal, Config};
+
+/// Rewrite method
+///
+/// Replaces every match in the lines of a file the search selects with `config.replace`, so
+/// `-v`, ranges, `--columns`, `-m` and the rest change the same lines and matches they would
+/// print. Other lines, line endings and whether the file ends with a newline are all kept as they were. The new
+/// contents are written to a temporary file beside the original, which is then renamed over
+/// it, so the file is never left half-written.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `path` - The file to rewrite
+///
+/// # Returns
+///
+/// `io::Result<usize>` - Number of lines changed, and the file is only written if that isn't 0
+pub fn rewrite(config: &Config, path: &Path) -> io::Result<usize> {
+    let original = fs::read_to_string(path)?;
+    let mut rewritten = String::with_capacity(original.len());
+    let mut changed = 0;
+    // Numbers of the lines selected, counted from 1 like the lines below whatever --line-base
+    // numbers them from, and no more of them than -m would print
+    let selected: HashSet<usize> = search_stream(config, original.as_bytes())?
+        .matches
+        .into_iter()
+        .take(config.per_file_max.unwrap_or(usize::MAX))
+        .map(|(number, _)| number - config.line_base)
+        .collect();
+    // A search stopped by Ctrl-C selected only some of the lines, so the file is left as it was
+    if signal::interrupted() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "rewrite interrupted"));
+    }
+
+    for (index, line) in original.split_inclusive('\n').enumerate() {
+        // The terminator, \r\n or \n, is put back as it was
+        let text = line.strip_suffix('\n').map_or(line, |text| text.strip_suffix('\r').unwrap_or(text));
+        let replaced = if selected.contains(&(index + 1)) { replace_matches(config, text) } else { Cow::Borrowed(text) };
+        if replaced != text {
+            changed += 1;
+        }
+        rewritten.push_str(&replaced);
+        rewritten.push_str(&line[text.len()..]);
+    }
+
+    if changed > 0 {
+        let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let temporary = path.with_file_name(format!(".{}.grep_remake.tmp", name));
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(rewritten.as_bytes())?;
+        file.sync_all()?;
+        // The rewritten file can be run or read by whoever could before
+        fs::set_permissions(&temporary, fs::metadata(path)?.permissions())?;
+        fs::rename(&temporary, path)?;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Rewrite test
+    ///
+    /// Test if only the matches change, unmatched lines and the missing final newline staying as they were
+    #[test]
+    fn rewrites_whole_file() {
+        let dir = env::temp_dir().join(format!("grep_remake_in_place_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        fs::write(&path, "colour one\r\nplain line\nColour and colour\nend").unwrap();
+
+        let filename = path.to_string_lossy();
+        let args: Vec<String> =
+            ["grep_remake", "--replace", "color", "--in-place", "colour", &filename].iter().map(|arg| arg.to_string()).collect();
+        let config = Config::new(&args).unwrap();
+
+        assert_eq!(2, rewrite(&config, &path).unwrap());
+        assert_eq!("color one\r\nplain line\nColour and color\nend", fs::read_to_string(&path).unwrap());
+        assert_eq!(vec![path.clone()], fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>());
+
+        // Nothing left to replace leaves the file alone
+        assert_eq!(0, rewrite(&config, &path).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Selected rewrite test
+    ///
+    /// Test if only the matches the search would select are replaced, here those in comments and ranges
+    #[test]
+    fn rewrites_selected_matches() {
+        let dir = env::temp_dir().join(format!("grep_remake_in_place_selected_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.rs");
+
+        let filename = path.to_string_lossy();
+        let rewritten = |extra: &[&str]| {
+            fs::write(&path, "let colour = 1; // colour it\nlet tint = colour;\n// BEGIN\ncolour\n").unwrap();
+            let mut args = vec!["grep_remake", "--replace", "color", "--in-place"];
+            args.extend_from_slice(extra);
+            args.extend_from_slice(&["colour", &filename]);
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            rewrite(&Config::new(&args).unwrap(), &path).unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+
+        assert_eq!("let colour = 1; // color it\nlet tint = colour;\n// BEGIN\ncolour\n", rewritten(&["--in-comments"]));
+        assert_eq!("let colour = 1; // colour it\nlet tint = colour;\n// BEGIN\ncolor\n", rewritten(&["--range-start", "BEGIN"]));
+        assert_eq!("let colour = 1; // colour it\nlet tint = colour;\n// BEGIN\ncolour\n", rewritten(&["-v"]));
+        assert_eq!("let colour = 1; // colour it\nlet tint = color;\n// BEGIN\ncolour\n", rewritten(&["--line-base", "10", "--range-start", "tint", "-m", "1"]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Numbered rewrite test
+    ///
+    /// Test if lines numbered from another base are still found, and -m leaves the matches after its limit alone
+    #[test]
+    fn rewrites_numbered_and_limited() {
+        let dir = env::temp_dir().join(format!("grep_remake_in_place_limited_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("f.txt");
+
+        let filename = path.to_string_lossy();
+        let rewritten = |extra: &[&str]| {
+            fs::write(&path, "foo\nbar\nfoo\nfoo\n").unwrap();
+            let mut args = vec!["grep_remake", "--replace", "X", "--in-place"];
+            args.extend_from_slice(extra);
+            args.extend_from_slice(&["foo", &filename]);
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            rewrite(&Config::new(&args).unwrap(), &path).unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+
+        assert_eq!("X\nbar\nX\nX\n", rewritten(&["--line-base", "10"]));
+        assert_eq!("X\nbar\nfoo\nfoo\n", rewritten(&["-m", "1"]));
+        assert_eq!("X\nbar\nX\nfoo\n", rewritten(&["--line-base", "10", "-m", "2"]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
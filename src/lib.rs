@@ -1,7 +1,272 @@
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::error::Error;
 use std::env;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod archive;
+mod balance;
+mod blame;
+mod chunked;
+mod code;
+mod decompress;
+mod error;
+mod estimate;
+mod file_type;
+mod follow;
+mod formatted;
+mod glob;
+mod in_place;
+pub mod rc;
+mod saved_pattern;
+#[cfg(unix)]
+mod serve;
+pub mod signal;
+pub mod template;
+pub mod terminal;
+mod timestamp;
+mod transform;
+
+pub use error::GrepError;
+pub use formatted::FormattedMatch;
+pub use transform::Transform;
+use saved_pattern::SavedPattern;
+use template::Template;
+
+/// Capacity of the buffer output is collected in before being written
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Matching lines from one source (a file or archive member), with the name output from
+/// it is prefixed with
+pub(crate) type Source = (Option<String>, Scan);
+
+/// Scan struct
+///
+/// What searching one stream of lines found
+///
+/// # Arguments
+///
+/// * `matches` - Line number (starting at 1) and text of each matching line
+/// * `denominator` - Number of lines searched that contain the `--denominator` pattern
+/// * `context` - Line number and text of the lines around the matches, for `-A`, `-B` and `-C`
+/// * `bytes_read` - Number of bytes read from the stream, for `--resume-from`
+/// * `unterminated_line` - Number of the last line read if it had no newline at the end
+/// * `sections` - Number and text of the section headers that matches come after, for `--section`
+/// * `timed_out` - The search stopped part way through, at the end of `--per-file-timeout`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Scan {
+    pub matches: Vec<(usize, String)>,
+    pub denominator: usize,
+    pub context: Vec<(usize, String)>,
+    pub bytes_read: u64,
+    pub unterminated_line: Option<usize>,
+    pub sections: Vec<(usize, String)>,
+    pub timed_out: bool,
+}
+
+/// Line kind enum
+///
+/// # Variants
+///
+/// * `Match` - A line that matched, printed with `:` separators
+/// * `Context` - A line printed around a match, printed with `-` separators
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LineKind {
+    Match,
+    Context,
+}
+
+/// Target struct
+///
+/// A file to search
+///
+/// # Arguments
+///
+/// * `path` - Where the file is opened from
+/// * `display` - Name the file is shown with in the output. Files found by walking a
+///   directory are shown relative to that directory, others as they were given.
+/// * `duplicates` - Display names of later files with the same contents, which `--dedup-files`
+///   reports alongside this one instead of searching
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Target {
+    pub path: String,
+    pub display: String,
+    pub duplicates: Vec<String>,
+}
+
+impl Target {
+    /// Target constructor
+    ///
+    /// # Parameters
+    ///
+    /// * `path` - A path given directly, which is displayed as it is
+    ///
+    /// # Returns
+    ///
+    /// * `Target` - The target
+    pub fn new(path: &str) -> Target {
+        Target { path: path.to_string(), display: path.to_string(), duplicates: Vec::new() }
+    }
+}
+
+/// File output struct
+///
+/// What searching one file produced
+///
+/// # Arguments
+///
+/// * `lines` - Lines to print
+/// * `note` - Message printed after the lines, which doesn't count towards any limits
+/// * `warning` - Why the file was skipped, written to the error stream
+/// * `name` - Display name of the file, for `--sort path`
+/// * `match_count` - Number of matches found in the file, for `--sort count`
+/// * `matched_bytes` - Total length of the matched text to be printed, for `--stats`
+/// * `end_offset` - Byte offset the search of the file stopped at, for `--resume-from`
+/// * `unterminated` - The last line is printed without a newline, as it had none in the file
+/// * `binary` - The file looked binary, for `--debug`
+/// * `timed_out` - Its search ran out of time, for `--debug`
+#[derive(Default)]
+struct FileOutput {
+    lines: Vec<String>,
+    note: Option<String>,
+    warning: Option<String>,
+    name: String,
+    match_count: usize,
+    matched_bytes: usize,
+    end_offset: Option<u64>,
+    unterminated: bool,
+    binary: bool,
+    timed_out: bool,
+}
+
+/// Stats struct
+///
+/// Totals for the `--stats` summary
+///
+/// # Arguments
+///
+/// * `files_searched` - Number of files searched
+/// * `matches` - Number of matches across all files
+/// * `file_bytes` - Display name and matched byte count of each file with matches, in output order
+/// * `extension_matches` - Number of matches in the files with each extension, for `--by-extension`
+/// * `files_failed` - Number of files that couldn't be searched, for the exit status
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Stats {
+    files_searched: usize,
+    files_failed: usize,
+    matches: usize,
+    file_bytes: Vec<(String, usize)>,
+    extension_matches: HashMap<String, usize>,
+}
+
+/// Output format enum
+///
+/// How matching lines are written out
+///
+/// # Variants
+///
+/// * `Text` - grep style `file:line:text` lines
+/// * `Csv` - `file,line_number,text` rows after a header row, quoted where needed
+/// * `Json` - A JSON object per line with the file, line number, text and the byte range of
+///   each match within the text, for clients that highlight matches themselves
+/// * `JsonArray` - The same objects as `Json` as the elements of a single JSON array, written
+///   as they are found and `[]` when nothing matches
+/// * `Markdown` - A `File | Line | Match` table for pasting into documents, with pipes in
+///   cells escaped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+    JsonArray,
+    Markdown,
+}
+
+/// Context style enum
+///
+/// How context lines are told apart from matched lines with `--color`
+///
+/// # Variants
+///
+/// * `Plain` - Both are printed in the terminal's usual style
+/// * `Dim` - Context lines are faint and matched lines bold, so the matches stand out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContextStyle {
+    #[default]
+    Plain,
+    Dim,
+}
+
+/// Binary mode enum
+///
+/// What happens to files that look binary, which is those with a NUL byte near the start
+///
+/// # Variants
+///
+/// * `Text` - Search them like any other file (`-a`, `--binary-files=text`)
+/// * `WithoutMatch` - Skip them as though they had no matches (`-I`, `--binary-files=without-match`)
+/// * `Binary` - Search them, but print `Binary file X matches` instead of their lines (the default)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryMode {
+    Text,
+    WithoutMatch,
+    Binary,
+}
+
+/// Long lines enum
+///
+/// What happens to a line longer than `--max-line-bytes`
+///
+/// # Variants
+///
+/// * `Skip` - Search the bytes up to the limit and pass over the rest of the line (the default)
+/// * `Error` - Stop searching the file with an error (`--long-lines error`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LongLines {
+    Skip,
+    Error,
+}
+
+/// Sort order enum
+///
+/// What order files are printed in
+///
+/// # Variants
+///
+/// * `Unsorted` - The order they were given or found in (or finished in, with `--no-sort`)
+/// * `Path` - Alphabetical order of their paths
+/// * `Count` - Most matches first, files with the same count keeping their order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Unsorted,
+    Path,
+    Count,
+}
+
+/// Search spec struct
+///
+/// One of several searches run in a single invocation, each for its own query in its own files
+///
+/// # Arguments
+///
+/// * `query` - The text searched for (`--search`)
+/// * `filenames` - The files it is searched for in (each `--in` after the `--search`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchSpec {
+    pub query: String,
+    pub filenames: Vec<String>,
+}
 
 /// Config struct
 /// 
@@ -10,192 +275,5804 @@ use std::env;
 /// # Arguments
 ///
 /// * `query` - The term being searched for
-/// * `filename` - The search file
-/// * `case_sensitive` - Environment variable for case sensitive/insensitive search
+/// * `filenames` - The search files
+/// * `case_sensitive` - Whether letter case must match. `-i` and `--case-sensitive` set it,
+///   otherwise the environment variable `CASE_INSENSITIVE` turns it off
+/// * `threads` - Number of worker threads, 1 searches the files sequentially and 0 (the default)
+///   uses one per CPU
+/// * `streaming` - Print parallel results as soon as a file is done instead of in argument order
+/// * `archives` - Search inside `.tar`, `.tar.gz` and `.tgz` files member by member
+/// * `decompress` - Search the decompressed contents of `.gz`, `.bz2` and `.xz` files. Files
+///   that can't be decompressed are skipped with a warning.
+/// * `recursive` - Search the files inside directory arguments and their subdirectories. None
+///   (neither `-r` nor `--no-recursive`) recurses only when the sole path argument is a directory
+/// * `files_with_matches` - Print only the names of files containing a match
+/// * `files_without_match` - Print only the names of files without a match (`-L`), empty files included
+/// * `list_dirs` - With `-l` or `-L`, print the directory of each file listed instead, each
+///   directory once in the order first found (`--dirnames`)
+/// * `null` - Follow filenames with a NUL byte, for `xargs -0`
+/// * `range_start` - Only search lines from one containing this, like sed's `/start/,/end/`
+/// * `range_end` - Close the range at the next line containing this, a later start reopens it
+/// * `files_from` - File listing the paths to search one per line (`-` for stdin), used instead of the filenames
+/// * `tab_width` - Expand tabs in printed lines to tab stops this far apart
+/// * `head_lines` - Only search the first this many lines of each file
+/// * `tail_lines` - Only search the last this many lines of each file
+/// * `line_number` - Prefix each line with its line number in the file
+/// * `strip_ansi` - Remove ANSI colour codes from lines before matching them
+/// * `print_stripped` - Print lines with the colour codes removed rather than as they were
+/// * `with_zero` - Print counts for files without any matches too, as `0` (`--with-zero`)
+/// * `count` - Print how many lines matched in each file instead of the lines. Combined with
+///   `only_matching` (`-oc`) this counts every match instead, so a line matching three times counts 3
+/// * `count_distinct` - Count only the different matching lines (or matches with `-o`) in each
+///   file, so repeats of a line count once (`--count-distinct`, which turns on `count`)
+/// * `span` - Print the first and last matching line numbers of each file with the count, as
+///   `file: first=12 last=340 count=9` (`--span`, which turns on `count`). With `with_zero`
+///   files without matches are printed as `file: none`.
+/// * `count_bytes` - Count the bytes of the matching lines (or matches with `-o`) in each file
+///   instead, their line terminators left out. These are UTF-8 bytes rather than characters, so
+///   `é` counts 2 (`--count-bytes`, which turns on `count`).
+/// * `count_per_pattern` - Count the matching lines of each file for every pattern separately,
+///   printed as `pattern: count` a line each, so a line with two of the patterns counts for
+///   both (`--count-per-pattern`, which turns on `count`)
+/// * `only_matching` - Print each match on its own line rather than the whole line
+/// * `format` - How matching lines are written out
+/// * `max_columns` - Cut longer lines to this many characters, ending them with `…`
+///   (`--max-columns`). Text lines are cut so they fit prefixes included, and Markdown rows
+///   have their text cut. The command line tool uses the terminal's width when this isn't
+///   given and stdout is a terminal, and `0` turns it off.
+/// * `per_file_max` - Print at most this many matches from any one file (`--max-per-file`, or
+///   `-m` as grep calls it). With `invert_match` these are the lines printed, the ones without
+///   the query, so `-v -m 3` prints three lines however many matching lines come between them.
+/// * `per_line_max` - Print at most this many matches from any one line with `only_matching`,
+///   the first ones in the line. Counts from `-oc` still include every match.
+/// * `total_max` - Print at most this many lines in total, across all files
+/// * `truncation_note` - Print `... (truncated)` after a file cut short by `per_file_max`
+/// * `unique_matches` - Print each distinct matched text once across all files, in the order first found
+/// * `denominator` - Print `matches/lines` for each file instead of the lines, where `lines` counts
+///   the searched lines containing this pattern, giving the match density of the file
+/// * `template` - Print each match through this template (`--format-template`) instead of the format
+/// * `preprocessor` - Command run with each file's path (`--pre`), whose output is searched instead
+///   of the file. Words after the first are passed before the path.
+/// * `before_context` - Print this many lines before each match
+/// * `after_context` - Print this many lines after each match
+/// * `mark_matches` - Start matched lines with `>` and other lines with a space, so the line
+///   that matched stands out within its context
+/// * `case_insensitive_globs` - Files whose path matches one of these globs (`--ignore-case-files`)
+///   are searched case insensitively, whatever `case_sensitive` says
+/// * `invert_match` - Select the lines that don't contain the query instead of those that do
+/// * `sort` - Order files are printed in. Sorting holds the output of every file in memory
+///   until the last has been searched, so nothing is printed before then.
+/// * `stats` - Print a summary after the output: the bytes of matched text in each file, then totals.
+///   With `--format json` it is a last JSON record instead, told apart from the matches by its
+///   `"type":"summary"` and giving the time taken too (`--json-summary` turns on both). The
+///   other formats only hold rows of matches, so it can't be used with them.
+/// * `path_separator` - Print paths with this between their components, replacing both `/`
+///   and `\`, so output looks the same on every platform. Files are still opened by their real path.
+/// * `show_line_occurrences` - Prefix each matched line with how many times it contains the query, as `(x3): `
+/// * `omit_single_occurrence` - Leave the `(x1): ` prefix off lines containing the query once
+/// * `binary_mode` - What happens to files that look binary
+/// * `binary_count` - Say how many matches a binary file has, as `Binary file X: N matches`,
+///   rather than only that it matches (`--binary-count`)
+/// * `display_limit` - Print at most this many lines (`--limit`), then say how many more there were.
+///   Unlike `total_max` the search carries on, so the count is of every remaining match.
+/// * `expressions` - Patterns given with `-e`, any of which a line may contain. With these every
+///   positional argument is a file, and `query` is the first of them.
+/// * `label_patterns` - Start each matched line with the patterns it contains, as `[foo,bar] `
+/// * `show_filename` - Prefix lines with their file (`-H`) or never do (`-h`). None prefixes them
+///   when the output could come from several files.
+/// * `dedup_files` - Search only the first of several files with the same contents, listing the
+///   others as its duplicates when it matches
+/// * `replace` - Print matched lines with each match replaced by this text (`--replace`). With
+///   `interpret_escapes` its escape sequences are read as well.
+/// * `in_place` - Rewrite each file with the matches in all its lines replaced, printing
+///   nothing (`--in-place`, which needs `replace`)
+/// * `max_line_bytes` - Hold at most this many bytes of a line in memory (`--max-line-bytes`),
+///   so a file with a huge line, such as a corrupt one without newlines, can't use it all up
+/// * `long_lines` - What happens to a line longer than `max_line_bytes` (`--long-lines skip`
+///   or `error`)
+/// * `lossy_utf8` - Replace invalid UTF-8 in files with U+FFFD and search the rest, rather than
+///   failing. Files that look binary are always read this way.
+/// * `group_separator` - Line printed between groups of context lines that aren't next to each
+///   other, `--` by default. An empty separator prints a blank line.
+/// * `resume_from` - Start searching each plain file this many bytes in (`--resume-from`), then report
+///   the offset the search ended at so the next run can carry on from there
+/// * `line_base` - Number of lines before the point the search starts, added to line numbers so
+///   they count from the start of the file rather than from `resume_from`
+/// * `paragraph_mode` - Print the whole paragraph (the lines between blank lines) around each
+///   matching line, followed by a blank line, instead of just the line (`--paragraph`)
+/// * `nth` - Print only the match at this position (`--nth`, starting at 1) counting across all
+///   files, then stop. Context isn't printed, and the exit status is 1 if there were fewer matches.
+/// * `first_match_per_file` - Print only the first matching line of each file as `path:line:text`
+///   (`--first-only`), reading no further into the file
+/// * `color` - Colour filenames, line numbers and the matched text with ANSI codes (`--color`)
+/// * `context_style` - How context lines differ from matched lines with `color`
+///   (`--color-context plain` or `dim`). Without `color` it changes nothing.
+/// * `excludes` - Files whose path (below the directory walked, for the files found in one)
+///   matches one of these globs (`--exclude`) aren't searched,
+///   unless a later `!` glob matches it too, as in `--exclude 'vendor/**' --exclude '!vendor/keep/**'`
+/// * `types` - Only search the files found in directories that are one of these file types,
+///   by extension, such as `rust` for `.rs` files (`-t`, `--type`)
+/// * `types_not` - Don't search the files found in directories that are one of these file
+///   types (`-T`, `--type-not`)
+/// * `searches` - Searches run one after the other instead of the query in the filenames, each
+///   printed under a `==> query <==` label (`--search query --in file`). Every other option
+///   applies to all of them.
+/// * `sort_numeric` - Print each file's matched lines in order of the integer they start with,
+///   rather than where they are in the file (`--sort-numeric`). Context isn't printed with it,
+///   as the lines around a match are no longer beside it.
+/// * `non_numeric_first` - Put lines that don't start with a number before the numbered ones
+///   instead of after them (`--non-numeric-first`, which needs `sort_numeric`)
+/// * `per_file_timeout` - Stop searching a file that has taken longer than this, keeping the
+///   matches found before then and warning that the rest of it wasn't searched
+///   (`--per-file-timeout`, in milliseconds). The time is checked every few hundred lines.
+/// * `debug` - Write a line to the error stream for each file saying whether it was searched,
+///   with its number of matches, or which option or check skipped it (`--debug`)
+/// * `path_substring` - Only search the files found in directories whose path below the directory
+///   contains this text, ignoring case unless `case_sensitive` (`--path-contains`)
+/// * `allow_empty` - Accept an empty pattern (`--allow-empty`), which matches every line
+/// * `column_range` - Only match within these byte columns of each line (`--columns 10:20`),
+///   counted from 1 and including both ends. The whole line is still printed, and lines
+///   shorter than the first column never match.
+/// * `no_trailing_newline` - Print a matched last line that has no newline in its file without
+///   one (`--no-trailing-newline`). Otherwise every printed line ends with exactly one newline,
+///   whatever the line ended with.
+/// * `blame` - End each matched line with the author and commit that last changed it, from
+///   `git blame` (`--blame`). Files git doesn't track are printed without.
+/// * `timestamp_format` - Read a timestamp in this format from the start of each matched line
+///   and print how long ago it was before the line, as `[3h ago] ` (`--relative-time`, with
+///   `--timestamp-format` for a format other than `%Y-%m-%d %H:%M:%S`). Lines not starting with
+///   one are printed as usual.
+/// * `collapse` - Print a run of identical matching lines, one straight after another, once
+///   with a ` (repeated 3 times)` suffix (`--collapse`)
+/// * `section_pattern` - Lines containing this are section headers (`--section`), and each match
+///   is printed after the last header before it, as `[database] matched line`. A leading `^`
+///   means the header must start with the rest.
+/// * `transforms` - Changes made to each line, in order, before it is matched (`--transform`,
+///   which takes a comma separated list and can be repeated). Lines are printed unchanged.
+/// * `save_pattern` - Write the patterns and case sensitivity to this file before searching
+///   (`--save-pattern`)
+/// * `load_pattern` - Read the patterns and case sensitivity from a file written by
+///   `--save-pattern` (`--load-pattern`), every positional argument then being a file
+/// * `serve` - Listen on a Unix socket at this path (`--serve`) and answer each connection's
+///   query and path with the matches as JSON lines, instead of searching the positionals
+/// * `interpret_escapes` - Read `\t`, `\n`, `\r`, `\0`, `\\` and `\xNN` (up to `\x7f`)
+///   in the query and `-e` patterns as the characters they stand for (`--escape`). Without it
+///   a backslash is just a backslash. Lines are matched one at a time without their line
+///   break, so a pattern containing `\n` can never match.
+/// * `in_comments` - Only count a match inside a comment (`--in-comments`), going by a
+///   line-at-a-time heuristic for C-style languages
+/// * `in_strings` - Only count a match inside a double quoted string (`--in-strings`). With
+///   `in_comments` as well, a match in either counts.
+/// * `by_extension` - After the search, print the number of matches in the files with each
+///   extension, most first, files without one grouped as `(none)` (`--by-extension`)
+/// * `query_stdin` - Read the query from stdin rather than the arguments, so every positional
+///   argument is a file (`--query-stdin`). It is read in full before any file is searched and
+///   then shared, unchanged, by every worker thread. As stdin can hold only one thing, it
+///   can't also be the list of files (`--files-from -`).
+/// * `trim` - Print each line without the whitespace at either end (`--trim`). Only the text
+///   is trimmed, after its prefixes are added, and lines are still matched as they are.
+/// * `match_context` - Print only this many characters either side of each match, with `…`
+///   where text was left out, rather than the whole line (`--match-context`)
+/// * `follow` - Keep watching the one file searched, printing matching lines as they are added
+///   to it until Ctrl-C (`-f`, `--follow`)
+/// * `output_buffer_size` - Bytes of output collected before they are written, 64 KiB when
+///   None (`--output-buffer-size`). Larger means fewer writes, smaller means lines show sooner.
+/// * `estimate` - Search only this percentage of the files, picked at random, and print how
+///   many matches all of them would have at the same rate (`--estimate`, `--dry-count`)
+/// * `balance` - Print only the nearest pair of these brackets holding each match, with `…`
+///   where text was left out, rather than the whole line (`--balance`, `()[]{}` unless
+///   `--balance-brackets` gives other pairs)
+/// * `seed` - Seed of the random picks of `estimate`, so a run can be repeated (`--seed`). A
+///   different one is used each run when None, and printed with the estimate.
+/// * `chunk_size` - Split a file longer than this many bytes into chunks of about this size
+///   ending at line breaks, and search them on separate threads (`--chunk-size`). Only done
+///   with more than one thread, and for options that look at each line on its own.
+/// * `absolute_paths` - Print the absolute path of each file rather than the path it was found by
+/// * `alternatives` - Split the query on this delimiter (`--alternatives`, `|` unless
+///   `--alternatives-delimiter` gives another) and match lines containing any of the parts
+#[derive(Clone)]
 pub struct Config {
     pub query: String,
-    pub filename: String,
+    pub filenames: Vec<String>,
     pub case_sensitive: bool,
+    pub threads: usize,
+    pub streaming: bool,
+    pub archives: bool,
+    pub decompress: bool,
+    pub recursive: Option<bool>,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    pub list_dirs: bool,
+    pub null: bool,
+    pub range_start: Option<String>,
+    pub range_end: Option<String>,
+    pub files_from: Option<String>,
+    pub tab_width: Option<usize>,
+    pub head_lines: Option<usize>,
+    pub tail_lines: Option<usize>,
+    pub line_number: bool,
+    pub strip_ansi: bool,
+    pub print_stripped: bool,
+    pub count: bool,
+    pub count_distinct: bool,
+    pub count_bytes: bool,
+    pub span: bool,
+    pub count_per_pattern: bool,
+    pub with_zero: bool,
+    pub only_matching: bool,
+    pub format: OutputFormat,
+    pub per_file_max: Option<usize>,
+    pub per_line_max: Option<usize>,
+    pub max_columns: Option<usize>,
+    pub total_max: Option<usize>,
+    pub truncation_note: bool,
+    pub unique_matches: bool,
+    pub denominator: Option<String>,
+    pub template: Option<Template>,
+    pub preprocessor: Option<String>,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub mark_matches: bool,
+    pub case_insensitive_globs: Vec<String>,
+    pub invert_match: bool,
+    pub sort: SortOrder,
+    pub alternatives: Option<String>,
+    pub stats: bool,
+    pub path_separator: Option<char>,
+    pub show_line_occurrences: bool,
+    pub omit_single_occurrence: bool,
+    pub binary_mode: BinaryMode,
+    pub binary_count: bool,
+    pub display_limit: Option<usize>,
+    pub expressions: Vec<String>,
+    pub label_patterns: bool,
+    pub show_filename: Option<bool>,
+    pub dedup_files: bool,
+    pub lossy_utf8: bool,
+    pub group_separator: String,
+    pub absolute_paths: bool,
+    pub resume_from: Option<u64>,
+    pub line_base: usize,
+    pub paragraph_mode: bool,
+    pub nth: Option<usize>,
+    pub first_match_per_file: bool,
+    pub color: bool,
+    pub context_style: ContextStyle,
+    pub excludes: Vec<String>,
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    pub path_substring: Option<String>,
+    pub debug: bool,
+    pub per_file_timeout: Option<Duration>,
+    pub sort_numeric: bool,
+    pub non_numeric_first: bool,
+    pub searches: Vec<SearchSpec>,
+    pub allow_empty: bool,
+    pub column_range: Option<(usize, usize)>,
+    pub no_trailing_newline: bool,
+    pub blame: bool,
+    pub timestamp_format: Option<String>,
+    pub collapse: bool,
+    pub section_pattern: Option<String>,
+    pub transforms: Vec<Transform>,
+    pub save_pattern: Option<String>,
+    pub load_pattern: Option<String>,
+    pub serve: Option<String>,
+    pub chunk_size: Option<u64>,
+    pub interpret_escapes: bool,
+    pub in_comments: bool,
+    pub in_strings: bool,
+    pub by_extension: bool,
+    pub query_stdin: bool,
+    pub trim: bool,
+    pub match_context: Option<usize>,
+    pub follow: bool,
+    pub output_buffer_size: Option<usize>,
+    pub estimate: Option<usize>,
+    pub seed: Option<u64>,
+    pub balance: Option<Vec<(char, char)>>,
+    pub replace: Option<String>,
+    pub in_place: bool,
+    pub max_line_bytes: Option<usize>,
+    pub long_lines: LongLines,
 }
 
 /// Config constructor
 ///
+/// Options may come before, between or after the query and files, and everything after a
+/// `--` is taken as positional
+///
 /// # Parameters
 ///
 /// * `args` - String array of arguments
 ///
 /// # Returns
 /// 
-/// * `Result<Config, &str>` - Config and simple error flag
+/// * `Result<Config, GrepError>` - Config, or a `Config` error for arguments that don't parse,
+///   an `InvalidPattern` error for a pattern that can't be searched for and an `Io` error for a
+///   saved pattern that can't be loaded
 impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &str> {
-        // Checks minimum arguments have been entered
-        if args.len() < 3 {
+    pub fn new(args: &[String]) -> Result<Config, GrepError> {
+        let mut config = Config::parse(args).map_err(|e| GrepError::Config(e.to_string()))?;
+        if let Some(path) = &config.load_pattern {
+            SavedPattern::load(Path::new(path)).map_err(|e| GrepError::at(path, e))?.apply(&mut config);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse method
+    ///
+    /// # Parameters
+    ///
+    /// * `args` - String array of arguments
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Config, &str>` - Config and simple error flag
+    fn parse(args: &[String]) -> Result<Config, &'static str> {
+        // Default options
+        let mut threads = 0;
+        let mut streaming = false;
+        let mut archives = false;
+        let mut decompress = false;
+        let mut recursive = None;
+        let mut files_with_matches = false;
+        let mut files_without_match = false;
+        let mut list_dirs = false;
+        let mut null = false;
+        let mut range_start = None;
+        let mut range_end = None;
+        let mut files_from = None;
+        let mut tab_width = None;
+        let mut head_lines = None;
+        let mut tail_lines = None;
+        let mut line_number = false;
+        let mut strip_ansi = false;
+        let mut print_stripped = false;
+        let mut count = false;
+        let mut with_zero = false;
+        let mut count_distinct = false;
+        let mut count_bytes = false;
+        let mut span = false;
+        let mut count_per_pattern = false;
+        let mut only_matching = false;
+        let mut format = OutputFormat::Text;
+        let mut per_file_max = None;
+        let mut per_line_max = None;
+        let mut max_columns = None;
+        let mut total_max = None;
+        let mut truncation_note = false;
+        let mut unique_matches = false;
+        let mut denominator = None;
+        let mut template = None;
+        let mut preprocessor = None;
+        let mut before_context = 0;
+        let mut after_context = 0;
+        let mut mark_matches = false;
+        let mut case_insensitive_globs = Vec::new();
+        let mut invert_match = false;
+        let mut sort = SortOrder::Unsorted;
+        let mut alternatives = None;
+        let mut stats = false;
+        let mut path_separator = None;
+        let mut show_line_occurrences = false;
+        let mut omit_single_occurrence = false;
+        let mut binary_mode = BinaryMode::Binary;
+        let mut binary_count = false;
+        let mut display_limit = None;
+        let mut expressions = Vec::new();
+        let mut label_patterns = false;
+        let mut show_filename = None;
+        let mut dedup_files = false;
+        let mut lossy_utf8 = false;
+        let mut absolute_paths = false;
+        let mut resume_from = None;
+        let mut line_base = 0;
+        let mut paragraph_mode = false;
+        let mut nth = None;
+        let mut first_match_per_file = false;
+        let mut color = false;
+        let mut context_style = ContextStyle::Plain;
+        let mut excludes = Vec::new();
+        let mut types = Vec::new();
+        let mut types_not = Vec::new();
+        let mut path_substring = None;
+        let mut debug = false;
+        let mut per_file_timeout = None;
+        let mut sort_numeric = false;
+        let mut non_numeric_first = false;
+        let mut searches: Vec<SearchSpec> = Vec::new();
+        let mut allow_empty = false;
+        let mut column_range = None;
+        let mut no_trailing_newline = false;
+        let mut blame = false;
+        let mut relative_time = false;
+        let mut timestamp_format = None;
+        let mut collapse = false;
+        let mut section_pattern = None;
+        let mut transforms = Vec::new();
+        let mut save_pattern = None;
+        let mut load_pattern = None;
+        let mut serve = None;
+        let mut chunk_size = None;
+        let mut interpret_escapes = false;
+        let mut in_comments = false;
+        let mut in_strings = false;
+        let mut by_extension = false;
+        let mut query_stdin = false;
+        let mut trim = false;
+        let mut match_context = None;
+        let mut follow = false;
+        let mut output_buffer_size = None;
+        let mut estimate = None;
+        let mut seed = None;
+        let mut balance = None;
+        let mut replace = None;
+        let mut in_place = false;
+        let mut max_line_bytes = None;
+        let mut long_lines = LongLines::Skip;
+        // Set by -i or --case-sensitive, otherwise taken from the environment
+        let mut case_sensitive = None;
+        let mut group_separator = "--".to_string();
+
+        // Arguments still to parse (first argument is the filepath to the executable). The flag
+        // marks the letters left over after taking the first from a group like "-oc".
+        let mut remaining: VecDeque<(String, bool)> = args.iter().skip(1).map(|arg| (arg.clone(), false)).collect();
+        // The query and files, in the order given, wherever they were among the options
+        let mut positionals = Vec::new();
+
+        // Parse options until a "--", setting positional arguments aside as they come
+        while let Some((arg, grouped)) = remaining.pop_front() {
+            let arg = if grouped { format!("-{}", arg) } else { arg };
+
+            // "--" marks the end of the options
+            if arg == "--" {
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                positionals.push(arg);
+                continue;
+            }
+
+            // Takes one letter from a group of short options, leaving the rest for the next
+            // option or, if this option takes one, as its value ("-j4")
+            let mut option = arg;
+            if !option.starts_with("--") && option.len() > 2 {
+                let rest = option.split_off(2);
+                remaining.push_front((rest, true));
+            }
+
+            // A long option may be given its value after an "=" ("--format=csv"), which it must then take
+            let mut inline_value = None;
+            if let Some(index) = option.find('=').filter(|_| option.starts_with("--")) {
+                let value = option.split_off(index + 1);
+                option.pop();
+                remaining.push_front((value, false));
+                inline_value = Some(remaining.len());
+            }
+
+            match option.as_str() {
+                "-j" | "--threads" => {
+                    threads = number_value(&mut remaining)?;
+                }
+                "--no-sort" => streaming = true,
+                "--archives" => archives = true,
+                "--no-archives" => archives = false,
+                "--decompress" => decompress = true,
+                "--no-decompress" => decompress = false,
+                "-r" | "--recursive" => recursive = Some(true),
+                "--no-recursive" => recursive = Some(false),
+                "-l" | "--files-with-matches" => files_with_matches = true,
+                "--no-files-with-matches" => files_with_matches = false,
+                "-L" | "--files-without-match" => files_without_match = true,
+                "--no-files-without-match" => files_without_match = false,
+                "--dirnames" => list_dirs = true,
+                "--no-dirnames" => list_dirs = false,
+                "--with-zero" => with_zero = true,
+                "--no-with-zero" => with_zero = false,
+                "-Z" | "--null" | "--print0-filenames" => null = true,
+                "--no-null" => null = false,
+                "--range-start" => range_start = Some(option_value(&mut remaining)?),
+                "--range-end" => range_end = Some(option_value(&mut remaining)?),
+                "--files-from" => files_from = Some(option_value(&mut remaining)?),
+                "--tabs" => {
+                    tab_width = match number_value(&mut remaining)? {
+                        0 => return Err("Tab width must be a positive number"),
+                        value => Some(value),
+                    };
+                }
+                "--head" => head_lines = Some(number_value(&mut remaining)?),
+                "--tail" => tail_lines = Some(number_value(&mut remaining)?),
+                "--strip-ansi" => strip_ansi = true,
+                "--no-strip-ansi" => strip_ansi = false,
+                "--print-stripped" => {
+                    strip_ansi = true;
+                    print_stripped = true;
+                }
+                "-n" | "--line-number" => line_number = true,
+                "--no-line-number" => line_number = false,
+                "-c" | "--count" => count = true,
+                "--no-count" => count = false,
+                "--count-distinct" => {
+                    count = true;
+                    count_distinct = true;
+                }
+                "--count-bytes" => {
+                    count = true;
+                    count_bytes = true;
+                }
+                "--span" => {
+                    count = true;
+                    span = true;
+                }
+                "--count-per-pattern" => {
+                    count = true;
+                    count_per_pattern = true;
+                }
+                "-o" | "--only-matching" => only_matching = true,
+                "--no-only-matching" => only_matching = false,
+                "--format" => {
+                    format = match option_value(&mut remaining)?.as_str() {
+                        "text" => OutputFormat::Text,
+                        "csv" => OutputFormat::Csv,
+                        "json" => OutputFormat::Json,
+                        "json-array" => OutputFormat::JsonArray,
+                        "markdown" => OutputFormat::Markdown,
+                        _ => return Err("Output format must be text, csv, json, json-array or markdown"),
+                    };
+                }
+                "-m" | "--max-count" | "--max-per-file" => per_file_max = Some(number_value(&mut remaining)?),
+                "--max-per-line" | "--max-matches-per-line" => per_line_max = Some(number_value(&mut remaining)?),
+                "--max-columns" => max_columns = Some(number_value(&mut remaining)?),
+                "--max-total" => total_max = Some(number_value(&mut remaining)?),
+                "--truncation-note" => truncation_note = true,
+                "--no-truncation-note" => truncation_note = false,
+                "--unique-matches" => unique_matches = true,
+                "--no-unique-matches" => unique_matches = false,
+                "--denominator" => denominator = Some(option_value(&mut remaining)?),
+                "--format-template" => template = Some(Template::parse(&option_value(&mut remaining)?)?),
+                "--pre" => preprocessor = Some(option_value(&mut remaining)?),
+                "-A" | "--after-context" => after_context = number_value(&mut remaining)?,
+                "-B" | "--before-context" => before_context = number_value(&mut remaining)?,
+                "-C" | "--context" => {
+                    after_context = number_value(&mut remaining)?;
+                    before_context = after_context;
+                }
+                "--mark-matches" => mark_matches = true,
+                "--no-mark-matches" => mark_matches = false,
+                "--ignore-case-files" => case_insensitive_globs.push(option_value(&mut remaining)?),
+                "-v" | "--invert-match" => invert_match = true,
+                "--no-invert-match" => invert_match = false,
+                "--sort" => {
+                    sort = match option_value(&mut remaining)?.as_str() {
+                        "none" => SortOrder::Unsorted,
+                        "path" => SortOrder::Path,
+                        "count" => SortOrder::Count,
+                        _ => return Err("Sort order must be none, path or count"),
+                    };
+                }
+                "--stats" => stats = true,
+                "--no-stats" => stats = false,
+                "--json-summary" => {
+                    stats = true;
+                    format = OutputFormat::Json;
+                }
+                "--occurrence-count" => show_line_occurrences = true,
+                "--no-occurrence-count" => show_line_occurrences = false,
+                "--binary-files" => {
+                    binary_mode = match option_value(&mut remaining)?.as_str() {
+                        "text" => BinaryMode::Text,
+                        "without-match" => BinaryMode::WithoutMatch,
+                        "binary" => BinaryMode::Binary,
+                        _ => return Err("Binary files type must be binary, without-match or text"),
+                    };
+                }
+                "-e" | "--regexp" => expressions.push(option_value(&mut remaining)?),
+                "--label-patterns" => label_patterns = true,
+                "--no-label-patterns" => label_patterns = false,
+                "-H" | "--with-filename" => show_filename = Some(true),
+                "-h" | "--no-filename" => show_filename = Some(false),
+                "--dedup-files" => dedup_files = true,
+                "--no-dedup-files" => dedup_files = false,
+                "--lossy" => lossy_utf8 = true,
+                "--absolute-paths" => absolute_paths = true,
+                "--no-absolute-paths" => absolute_paths = false,
+                "--resume-from" => resume_from = Some(number_value(&mut remaining)? as u64),
+                "--line-base" => line_base = number_value(&mut remaining)?,
+                "--paragraph" => paragraph_mode = true,
+                "--no-paragraph" => paragraph_mode = false,
+                "--color" | "--colour" => color = true,
+                "--no-color" | "--no-colour" => color = false,
+                "--color-context" | "--colour-context" => {
+                    context_style = match option_value(&mut remaining)?.as_str() {
+                        "plain" => ContextStyle::Plain,
+                        "dim" => ContextStyle::Dim,
+                        _ => return Err("Context style must be plain or dim"),
+                    };
+                }
+                "--exclude" => excludes.push(option_value(&mut remaining)?),
+                "-t" | "--type" => types.push(option_value(&mut remaining)?),
+                "-T" | "--type-not" => types_not.push(option_value(&mut remaining)?),
+                "--path-contains" => path_substring = Some(option_value(&mut remaining)?),
+                "--debug" => debug = true,
+                "--no-debug" => debug = false,
+                "--per-file-timeout" => {
+                    per_file_timeout = match number_value(&mut remaining)? {
+                        0 => return Err("Per file timeout must be more than 0"),
+                        millis => Some(Duration::from_millis(millis as u64)),
+                    };
+                }
+                "--sort-numeric" => sort_numeric = true,
+                "--no-sort-numeric" => sort_numeric = false,
+                "--non-numeric-first" => non_numeric_first = true,
+                "--no-non-numeric-first" => non_numeric_first = false,
+                "--search" => searches.push(SearchSpec { query: option_value(&mut remaining)?, filenames: Vec::new() }),
+                "--in" => {
+                    let file = option_value(&mut remaining)?;
+                    searches.last_mut().ok_or("--in must follow a --search")?.filenames.push(file);
+                }
+                "--allow-empty" => allow_empty = true,
+                "--no-allow-empty" => allow_empty = false,
+                "--no-trailing-newline" => no_trailing_newline = true,
+                "--trailing-newline" => no_trailing_newline = false,
+                "--blame" => blame = true,
+                "--no-blame" => blame = false,
+                "--relative-time" => relative_time = true,
+                "--no-relative-time" => relative_time = false,
+                "--collapse" => collapse = true,
+                "--no-collapse" => collapse = false,
+                "--section" => section_pattern = Some(option_value(&mut remaining)?),
+                "--save-pattern" => save_pattern = Some(option_value(&mut remaining)?),
+                "--load-pattern" => load_pattern = Some(option_value(&mut remaining)?),
+                "--serve" => serve = Some(option_value(&mut remaining)?),
+                "--escape" => interpret_escapes = true,
+                "--no-escape" => interpret_escapes = false,
+                "--in-comments" => in_comments = true,
+                "--no-in-comments" => in_comments = false,
+                "--in-strings" => in_strings = true,
+                "--no-in-strings" => in_strings = false,
+                "--by-extension" => by_extension = true,
+                "--no-by-extension" => by_extension = false,
+                "--query-stdin" => query_stdin = true,
+                "--no-query-stdin" => query_stdin = false,
+                "--trim" => trim = true,
+                "--no-trim" => trim = false,
+                "--match-context" => match_context = Some(number_value(&mut remaining)?),
+                "-f" | "--follow" => follow = true,
+                "--no-follow" => follow = false,
+                "--output-buffer-size" => output_buffer_size = Some(number_value(&mut remaining)?),
+                "--estimate" | "--dry-count" => {
+                    estimate = match number_value(&mut remaining)? {
+                        percent @ 1..=100 => Some(percent),
+                        _ => return Err("Estimate sample must be a percentage from 1 to 100"),
+                    };
+                }
+                "--seed" => seed = Some(number_value(&mut remaining)? as u64),
+                "--replace" => replace = Some(option_value(&mut remaining)?),
+                "--in-place" => in_place = true,
+                "--no-in-place" => in_place = false,
+                "--max-line-bytes" => {
+                    max_line_bytes = match number_value(&mut remaining)? {
+                        0 => return Err("Max line bytes must be more than 0"),
+                        limit => Some(limit),
+                    };
+                }
+                "--long-lines" => {
+                    long_lines = match option_value(&mut remaining)?.as_str() {
+                        "skip" => LongLines::Skip,
+                        "error" => LongLines::Error,
+                        _ => return Err("Long lines must be skip or error"),
+                    };
+                }
+                "--balance" => {
+                    balance = balance.or_else(|| balance::parse_pairs(balance::DEFAULT_BRACKETS));
+                }
+                "--balance-brackets" => {
+                    let pairs = balance::parse_pairs(&option_value(&mut remaining)?);
+                    balance = Some(pairs.ok_or("Balance brackets must be pairs of different characters, like ()[]")?);
+                }
+                "--chunk-size" => {
+                    chunk_size = match number_value(&mut remaining)? {
+                        0 => return Err("Chunk size must be at least 1"),
+                        size => Some(size as u64),
+                    };
+                }
+                "--transform" => {
+                    for name in option_value(&mut remaining)?.split(',') {
+                        transforms.push(Transform::parse(name).ok_or("Transform must be lowercase, trim or basename")?);
+                    }
+                }
+                "--timestamp-format" => timestamp_format = Some(option_value(&mut remaining)?),
+                "--columns" => {
+                    let value = option_value(&mut remaining)?;
+                    let (start, end) = value.split_once(':').ok_or("Columns must be given as start:end")?;
+                    column_range = match (start.parse(), end.parse()) {
+                        (Ok(start), Ok(end)) if 1 <= start && start <= end => Some((start, end)),
+                        _ => return Err("Columns must be numbers from 1, the start no greater than the end"),
+                    };
+                }
+                "-i" | "--ignore-case" => case_sensitive = Some(false),
+                "--case-sensitive" => case_sensitive = Some(true),
+                "--first-only" => {
+                    // The location is the point, so the line number is always shown
+                    first_match_per_file = true;
+                    line_number = true;
+                }
+                "--nth" => {
+                    nth = match number_value(&mut remaining)? {
+                        0 => return Err("Match position must be at least 1"),
+                        position => Some(position),
+                    };
+                }
+                "--group-separator" => group_separator = option_value(&mut remaining)?,
+                "--limit" => display_limit = Some(number_value(&mut remaining)?),
+                "-a" | "--text" => binary_mode = BinaryMode::Text,
+                "-I" => binary_mode = BinaryMode::WithoutMatch,
+                "--binary-count" => binary_count = true,
+                "--no-binary-count" => binary_count = false,
+                "--omit-single-occurrence" => {
+                    show_line_occurrences = true;
+                    omit_single_occurrence = true;
+                }
+                "--path-separator" => {
+                    let value = option_value(&mut remaining)?;
+                    let mut chars = value.chars();
+                    path_separator = match (chars.next(), chars.next()) {
+                        (Some(separator), None) => Some(separator),
+                        _ => return Err("Path separator must be a single character"),
+                    };
+                }
+                "--alternatives" => {
+                    alternatives.get_or_insert_with(|| "|".to_string());
+                }
+                "--alternatives-delimiter" => {
+                    alternatives = match option_value(&mut remaining)? {
+                        delimiter if delimiter.is_empty() => return Err("Alternatives delimiter must not be empty"),
+                        delimiter => Some(delimiter),
+                    };
+                }
+                _ => return Err("Unrecognised option"),
+            }
+
+            if inline_value == Some(remaining.len()) {
+                return Err("Option does not take a value");
+            }
+        }
+
+        // Checks minimum arguments have been entered (the files may come from a list instead)
+        positionals.extend(remaining.into_iter().map(|(arg, _)| arg));
+        let mut positionals = positionals.into_iter();
+        let query = match expressions.first() {
+            Some(first) => first.clone(),
+            // A loaded pattern is filled in by Config::new, a query on stdin is read by run, a
+            // server is sent its queries, and each --search has its own
+            None if load_pattern.is_some() || serve.is_some() || query_stdin || !searches.is_empty() => String::new(),
+            None => positionals.next().ok_or("Some arguments appear to be missing")?,
+        };
+        let filenames: Vec<String> = positionals.collect();
+
+        // With --escape the patterns are searched for with their escape sequences turned into characters
+        let (query, expressions, replace) = if interpret_escapes {
+            let expressions = expressions.iter().map(|pattern| unescape_query(pattern)).collect::<Result<_, _>>()?;
+            for search in &mut searches {
+                search.query = unescape_query(&search.query)?;
+            }
+            (unescape_query(&query)?, expressions, replace.as_deref().map(unescape_query).transpose()?)
+        } else {
+            (query, expressions, replace)
+        };
+        if in_place && replace.is_none() {
+            return Err("--in-place needs --replace");
+        }
+        if non_numeric_first && !sort_numeric {
+            return Err("--non-numeric-first needs --sort-numeric");
+        }
+
+        // A timestamp format only does something with --relative-time
+        let timestamp_format =
+            relative_time.then(|| timestamp_format.unwrap_or_else(|| timestamp::DEFAULT_FORMAT.to_string()));
+        if filenames.is_empty() && files_from.is_none() && serve.is_none() && searches.is_empty() {
             return Err("Some arguments appear to be missing");
         }
-        
-        // Get relevant arguments (first argument is the filepath to the executable)
-        let query = args[1].clone();
-        let filename = args[2].clone();
+        if searches.iter().any(|search| search.filenames.is_empty()) {
+            return Err("Each --search needs an --in file to search");
+        }
+        if query_stdin && !expressions.is_empty() {
+            return Err("--query-stdin can't be used with -e");
+        }
+        if query_stdin && files_from.as_deref() == Some("-") {
+            return Err("Stdin can't hold both the query and the list of files");
+        }
 
-        // Get environment variable "CASE_INSENSITIVE"
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+        // Without -i or --case-sensitive, get environment variable "CASE_INSENSITIVE"
+        let case_sensitive = case_sensitive.unwrap_or_else(|| env::var("CASE_INSENSITIVE").is_err());
         
         // Create Config and return it with an Ok wrapper
-        Ok(Config { query, filename, case_sensitive })
+        Ok(Config {
+            query,
+            filenames,
+            case_sensitive,
+            threads,
+            streaming,
+            archives,
+            decompress,
+            recursive,
+            files_with_matches,
+            files_without_match,
+            list_dirs,
+            null,
+            range_start,
+            range_end,
+            files_from,
+            tab_width,
+            head_lines,
+            tail_lines,
+            line_number,
+            strip_ansi,
+            print_stripped,
+            count,
+            count_distinct,
+            count_bytes,
+            span,
+            count_per_pattern,
+            with_zero,
+            only_matching,
+            format,
+            per_file_max,
+            per_line_max,
+            max_columns,
+            total_max,
+            truncation_note,
+            unique_matches,
+            denominator,
+            template,
+            preprocessor,
+            before_context,
+            after_context,
+            mark_matches,
+            case_insensitive_globs,
+            invert_match,
+            sort,
+            alternatives,
+            stats,
+            path_separator,
+            show_line_occurrences,
+            omit_single_occurrence,
+            binary_mode,
+            binary_count,
+            display_limit,
+            expressions,
+            label_patterns,
+            show_filename,
+            dedup_files,
+            lossy_utf8,
+            group_separator,
+            absolute_paths,
+            resume_from,
+            line_base,
+            paragraph_mode,
+            nth,
+            first_match_per_file,
+            color,
+            context_style,
+            excludes,
+            types,
+            types_not,
+            path_substring,
+            debug,
+            per_file_timeout,
+            sort_numeric,
+            non_numeric_first,
+            searches,
+            allow_empty,
+            column_range,
+            no_trailing_newline,
+            blame,
+            timestamp_format,
+            collapse,
+            section_pattern,
+            transforms,
+            save_pattern,
+            load_pattern,
+            serve,
+            chunk_size,
+            interpret_escapes,
+            in_comments,
+            in_strings,
+            by_extension,
+            query_stdin,
+            trim,
+            match_context,
+            follow,
+            output_buffer_size,
+            estimate,
+            seed,
+            balance,
+            replace,
+            in_place,
+            max_line_bytes,
+            long_lines,
+        })
+    }
+
+    /// Validate method
+    ///
+    /// Rejects combinations of options that parse but can't be what was meant. An empty
+    /// pattern is in every line, so it is usually an unset shell variable rather than a
+    /// request to print the whole file.
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), GrepError>` - Simple error flag
+    pub fn validate(&self) -> Result<(), GrepError> {
+        let patterns = self.patterns();
+        // A server's patterns come with each request, a query on stdin is checked once read, and
+        // separate searches are checked on their own
+        let unread = self.serve.is_some() || (self.query_stdin && self.query.is_empty()) || !self.searches.is_empty();
+        let empty_search = self.searches.iter().any(|search| search.query.is_empty());
+        let empty = empty_search || (!unread && (patterns.is_empty() || patterns.iter().any(|pattern| pattern.is_empty())));
+        if empty && !self.allow_empty {
+            return Err(GrepError::InvalidPattern("empty pattern, use --allow-empty to match every line".to_string()));
+        }
+        if let Some(unknown) = self.types.iter().chain(&self.types_not).find(|name| file_type::extensions(name).is_none()) {
+            return Err(GrepError::Config(format!("Unknown file type {}, known types are: {}", unknown, file_type::known())));
+        }
+        // Each --search brings its own query and files, so any others would never be searched
+        if !self.searches.is_empty() && (!self.filenames.is_empty() || !self.expressions.is_empty() || self.files_from.is_some()) {
+            return Err(GrepError::Config("With --search, patterns and files are given by --search and --in".to_string()));
+        }
+        let counts = self.count || self.denominator.is_some();
+        // Following prints each matching line as it arrives, and nothing else
+        let other_output = counts || self.lists_files() || self.only_matching || self.unique_matches || self.template.is_some();
+        let grouped = self.before_context > 0 || self.after_context > 0 || self.paragraph_mode;
+        if self.follow && (other_output || grouped || self.format != OutputFormat::Text) {
+            return Err(GrepError::Config("--follow only prints matching lines, so can't be used with -c, -l, -o, context or --format".to_string()));
+        }
+        // A JSON array holds nothing but match objects, which counts and the stats block aren't
+        if self.format == OutputFormat::JsonArray && (counts || self.stats) {
+            return Err(GrepError::Config("-c and --stats can't be used with --format json-array".to_string()));
+        }
+        // Nor do tables have anywhere to put the stats, only a JSON stream has a record for them
+        if self.stats && matches!(self.format, OutputFormat::Csv | OutputFormat::Markdown) {
+            return Err(GrepError::Config("--stats can only be used with --format text or json".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Read query method
+    ///
+    /// Takes the query from a reader, for `--query-stdin`. A single line break at the end is
+    /// dropped, as `echo` adds one, and with `--escape` the query's escape sequences are read.
+    ///
+    /// # Parameters
+    ///
+    /// `reader` - Source of the query, stdin for the command line tool
+    ///
+    /// # Returns
+    ///
+    /// `Result<(), GrepError>` - Simple error flag, for a query that can't be read or is empty
+    pub fn read_query<R: Read>(&mut self, mut reader: R) -> Result<(), GrepError> {
+        let mut query = String::new();
+        reader.read_to_string(&mut query)?;
+        let query = query.strip_suffix('\n').map_or(query.as_str(), |query| query.strip_suffix('\r').unwrap_or(query));
+
+        self.query = if self.interpret_escapes {
+            unescape_query(query).map_err(|e| GrepError::InvalidPattern(e.to_string()))?
+        } else {
+            query.to_string()
+        };
+        // Checked here as validate had to let the query be empty until now
+        if self.query.is_empty() && !self.allow_empty {
+            return Err(GrepError::InvalidPattern("empty query on stdin, use --allow-empty to match every line".to_string()));
+        }
+        self.validate()
+    }
+
+    /// Lists files method
+    ///
+    /// # Returns
+    ///
+    /// `bool` - True when only filenames are printed, with `-l` or `-L`
+    pub fn lists_files(&self) -> bool {
+        self.files_with_matches || self.files_without_match
+    }
+
+    /// Patterns method
+    ///
+    /// # Returns
+    ///
+    /// `Vec<&str>` - The texts a line is searched for: the `-e` patterns, or the query
+    /// without them, each split into its non-empty alternatives with `--alternatives`
+    pub fn patterns(&self) -> Vec<&str> {
+        let given = if self.expressions.is_empty() { std::slice::from_ref(&self.query) } else { &self.expressions[..] };
+
+        match &self.alternatives {
+            Some(delimiter) => given
+                .iter()
+                .flat_map(|pattern| pattern.split(delimiter.as_str()))
+                .filter(|part| !part.is_empty())
+                .collect(),
+            None => given.iter().map(String::as_str).collect(),
+        }
     }
 }
 
-/// Run method
+/// Option value method
 ///
-/// Method that gets search content and decides what search type to run
+/// Takes the argument following an option as its value
 ///
 /// # Parameters
 ///
-/// `config` - The given config of the execution
+/// * `remaining` - Arguments still to parse
 ///
 /// # Returns
 ///
-/// `Result<(), Box<dyn Error>>` - Simple error flag 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    // Gets contents from the given file
-    let contents = fs::read_to_string(config.filename)
-        // Error handling
-        .expect("Something went wrong reading the file");
+/// * `Result<String, &str>` - The value and simple error flag
+fn option_value(remaining: &mut VecDeque<(String, bool)>) -> Result<String, &'static str> {
+    remaining.pop_front().map(|(value, _)| value).ok_or("Option is missing its value")
+}
 
-    // Gets the results of the search
-    let results = if config.case_sensitive {
-        // If case sensitive use search()
-        search(&config.query, &contents)
-    } else {
-        // If case insensitive use search_case_insensitive()
-        search_case_insensitive(&config.query, &contents)
-    };
+/// Number value method
+///
+/// Takes the argument following an option as a number
+///
+/// # Parameters
+///
+/// * `remaining` - Arguments still to parse
+///
+/// # Returns
+///
+/// * `Result<usize, &str>` - The number and simple error flag
+fn number_value(remaining: &mut VecDeque<(String, bool)>) -> Result<usize, &'static str> {
+    option_value(remaining)?.parse().map_err(|_| "Option value must be a number")
+}
 
-    // Output results
-    for line in results {
-        println!("{}", line);
+/// Unescape query method
+///
+/// # Parameters
+///
+/// * `text` - A pattern given with `--escape`
+///
+/// # Returns
+///
+/// * `Result<String, &str>` - The pattern with each escape sequence replaced by its character,
+///   and simple error flag for a sequence that isn't one of them
+fn unescape_query(text: &str) -> Result<String, &'static str> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next() {
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('x') => {
+                // Exactly two hex digits, and only up to 7f as anything higher isn't a whole UTF-8 character
+                let digits: String = chars.by_ref().take(2).collect();
+                let hex = digits.len() == 2 && digits.chars().all(|digit| digit.is_ascii_hexdigit());
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if hex && byte.is_ascii() => char::from(byte),
+                    _ => return Err("Escape \\x must be followed by two hex digits up to 7f"),
+                }
+            }
+            _ => return Err("Unknown escape sequence, use \\\\ for a backslash"),
+        });
     }
-
-    // Return Ok error flag
-    Ok(())
+    Ok(unescaped)
 }
 
-/// Search method
+/// Run method
 ///
-/// Performs case sensitive search
+/// Method that gets search content and decides what search type to run. Output is
+/// buffered and flushed once at the end, including when the search stops with an error.
 ///
-/// # Arguments
+/// # Parameters
 ///
-/// * `query` - The search query -- see Config for more information
-/// * `contents` - Contents of the file
+/// `config` - The given config of the execution
+/// `out` - Where the results are written, stdout for the command line tool
+/// `err` - Where warnings about skipped files are written, stderr for the command line tool
 ///
 /// # Returns
 ///
-/// `Vec<&'a str>` - Vector of lines that contain the search query
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    // Builds results vector
-    let mut results = Vec::new();
+/// `Result<i32, GrepError>` - Exit status for the process, or an error
+pub fn run(mut config: Config, out: &mut dyn Write, err: &mut dyn Write) -> Result<i32, GrepError> {
+    if let Some(path) = &config.serve {
+        #[cfg(unix)]
+        return serve::serve(&config, Path::new(path))
+            .map(|_| if signal::interrupted() { signal::INTERRUPTED_STATUS } else { 0 })
+            .map_err(GrepError::from);
+        #[cfg(not(unix))]
+        return Err(GrepError::Config(format!("{}: --serve needs Unix sockets", path)));
+    }
 
-    // Iterates through contents lines
-    for line in contents.lines() {
-        // Check if the line contains the query
-        if line.contains(query) {
-            // If it does push it to results vector
-            results.push(line);
-        }
+    // The query is read before any worker thread starts, so none of them touch stdin
+    if config.query_stdin && config.query.is_empty() {
+        config.read_query(io::stdin().lock())?;
     }
 
-    // Returns results
-    results
+    // Collects small writes into large ones rather than writing each line separately
+    let mut out = BufWriter::with_capacity(config.output_buffer_size.unwrap_or(OUTPUT_BUFFER_SIZE), out);
+    let status = if config.searches.is_empty() { search_all(&config, &mut out, err) } else { search_each(&config, &mut out, err) };
+
+    // Whatever was found before any error (or Ctrl-C) is still written out
+    out.flush()?;
+
+    if signal::interrupted() {
+        return Ok(signal::INTERRUPTED_STATUS);
+    }
+    status
 }
 
-/// Search method -- case insenitive
+/// Search each method
 ///
-/// Performs a case insensitive search
+/// Runs each `--search` on its own files with the rest of the options, printing its output
+/// under a label with its query, a blank line between one search and the next
 ///
-/// # Arguments
+/// # Parameters
 ///
-/// * `query` - The search query -- see Config for more information
-/// * `contents` - Contents of the file
+/// `config` - The given config of the execution
+/// `out` - Where the results are written
+/// `err` - Where warnings about skipped files are written
 ///
 /// # Returns
 ///
-/// `Vec<&'a str>` - Vector of lines that contain the search query
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    // Changes query to lowercase
-    let query = query.to_lowercase();
+/// `Result<i32, GrepError>` - Exit status for the process, 0 if any search found something
+fn search_each(config: &Config, out: &mut dyn Write, err: &mut dyn Write) -> Result<i32, GrepError> {
+    let mut statuses = Vec::new();
+    for (index, search) in config.searches.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "==> {} <==", search.query)?;
 
-    // See search() for other comments
-    let mut results = Vec::new();
+        let single = Config {
+            query: search.query.clone(),
+            expressions: Vec::new(),
+            filenames: search.filenames.clone(),
+            files_from: None,
+            searches: Vec::new(),
+            ..config.clone()
+        };
+        statuses.push(search_all(&single, out, err)?);
+    }
+    Ok(if statuses.contains(&0) { 0 } else { statuses.into_iter().max().unwrap_or(1) })
+}
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
+/// Search all method
+///
+/// Searches every file given by the config
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `out` - Where the results are written
+/// `err` - Where warnings about skipped files are written
+///
+/// # Returns
+///
+/// `Result<i32, GrepError>` - Exit status for the process, 0 when something matched, 1 when
+/// nothing did and 2 when nothing could be searched, or an error
+fn search_all(config: &Config, out: &mut dyn Write, err: &mut dyn Write) -> Result<i32, GrepError> {
+    let started = Instant::now();
+    if let Some(path) = &config.save_pattern {
+        SavedPattern::from_config(config).save(Path::new(path)).map_err(|e| GrepError::at(path, e))?;
+    }
+
+    // Expand directories into the files beneath them
+    let (mut files, skipped) = collect_files(config, err)?;
+    // Globs are matched against paths below the directory walked, so `vendor/**` works from any root
+    let (kept, excluded): (Vec<Target>, Vec<Target>) =
+        files.into_iter().partition(|target| !glob::excluded(&config.excludes, &target.display));
+    files = kept;
+    if config.debug {
+        for target in excluded {
+            writeln!(err, "grep_remake: debug: {}: skipped (--exclude)", target.display)?;
+        }
+    }
+    for target in &mut files {
+        if config.absolute_paths {
+            target.display = absolute_path(&target.path);
+        }
+        target.display = display_path(config, &target.display);
+    }
+    if config.dedup_files {
+        files = dedup_files(files, err)?;
+        if config.debug {
+            for target in &files {
+                for duplicate in &target.duplicates {
+                    writeln!(err, "grep_remake: debug: {}: skipped (same contents as {})", duplicate, target.display)?;
+                }
+            }
         }
     }
 
-    results
-}
+    // Like grep, fail if nothing given on the command line could be searched
+    if files.is_empty() && skipped > 0 {
+        return Ok(2);
+    }
 
- 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // Lines are prefixed with their file when the output could come from several
+    let show_filename = config
+        .show_filename
+        .unwrap_or_else(|| recurses(config) || config.files_from.is_some() || config.filenames.len() > 1);
 
-    /// One result test
-    ///
-    /// Test if the correct result is returned when one is desired
-    #[test]
-    fn one_result() {
-        let query = "Testing";
-        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
-        
-        assert_eq!(
-            vec!["It contains a line that says Testing which should be found by the program"], 
-            search(query, contents)
-        );
+    // A followed file is searched as it grows, so there is no end to print a summary at
+    if config.follow {
+        let target = match &files[..] {
+            [target] => target,
+            _ => return Err(GrepError::Config("--follow needs exactly one file".to_string())),
+        };
+        let name = if show_filename { Some(target.display.as_str()) } else { None };
+        let printed = follow::follow(config, &target.path, name, out, &signal::interrupted).map_err(|e| GrepError::at(&target.display, e))?;
+        return Ok(if printed > 0 { 0 } else { 1 });
     }
 
-    /// Multiple result test
-    ///
-    /// Test if the correct result is returned when several are desired
-    #[test]
-    fn multiple_result() {
-        let query = "contains";
-        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
-        
-        assert_eq!(
-            vec!["It contains a line that says Testing which should be found by the program", "It also contains another LINE that does not contain the above term that should not be found"], 
-            search(query, contents)
-        );
+    // Files are rewritten rather than having anything printed from them
+    if config.in_place {
+        let mut changed = 0;
+        for target in &files {
+            changed += in_place::rewrite(config, Path::new(&target.path)).map_err(|e| GrepError::at(&target.display, e))?;
+        }
+        return Ok(if changed > 0 { 0 } else { 1 });
     }
 
-    /// Case sensitive result test
-    ///
-    /// Test if the correct result is returned case sensitive
-    #[test]
-    fn case_sensitive() {
-        let query = "line";
-        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
+    // Only the sampled files are searched, and nothing of them is printed but the estimate
+    if let Some(percent) = config.estimate {
+        let seed = config.seed.unwrap_or_else(estimate::clock_seed);
+        let sampled = estimate::sample(files.len(), percent, seed);
+        let mut matches = 0;
+        for &index in &sampled {
+            matches += search_file(config, &files[index], show_filename)?.match_count;
+        }
 
-        assert_eq!(
-            vec!["It contains a line that says Testing which should be found by the program"], 
-            search(query, contents)
-        );
+        let total = estimate::extrapolate(matches, sampled.len(), files.len());
+        writeln!(out, "~{} matches (from {} of {} files, seed {})", total, sampled.len(), files.len(), seed)?;
+        return Ok(if matches > 0 { 0 } else { 1 });
     }
 
-    /// Case sensitive result test
-    ///
-    /// Test if the correct result is returned case insensitive
-    #[test]
-    fn case_insensitive() {
-        let query = "lInE";
-        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
+    // CSV and Markdown start with a header naming their columns, and a JSON array with its bracket
+    match config.format {
+        OutputFormat::Csv if config.count || config.denominator.is_some() => writeln!(out, "file,count")?,
+        OutputFormat::Csv => writeln!(out, "file,line_number,text")?,
+        OutputFormat::Markdown => writeln!(out, "| File | Line | Match |\n| --- | --- | --- |")?,
+        OutputFormat::JsonArray => write!(out, "[")?,
+        _ => {}
+    }
 
-        assert_eq!(
+    let mut printer = Printer { config, out, err, printed: 0, hidden: 0, skipped: 0, seen: HashSet::new(), stats: Stats::default() };
+
+    // With --sort every file's output is held back until all have been searched
+    let mut held = Vec::new();
+    let mut emit = |output: FileOutput| -> io::Result<bool> {
+        if config.sort == SortOrder::Unsorted {
+            return printer.print(output);
+        }
+        held.push(output);
+        Ok(true)
+    };
+
+    if worker_count(config) > 1 && files.len() > 1 {
+        // Hand multiple files to the worker threads when asked to
+        search_parallel(config, &files, show_filename, &mut |output| emit(output).map(|_| ()))?;
+    } else {
+        // Search each file in turn, stopping once --max-total is reached or on Ctrl-C
+        for target in &files {
+            if signal::interrupted() || !emit(search_file(config, target, show_filename)?)? {
+                break;
+            }
+        }
+    }
+
+    // Sorting is stable, so files that compare equal stay in the order they were searched
+    match config.sort {
+        SortOrder::Unsorted => {}
+        SortOrder::Path => held.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortOrder::Count => held.sort_by_key(|output| std::cmp::Reverse(output.match_count)),
+    }
+    for output in held {
+        if !printer.print(output)? {
+            break;
+        }
+    }
+
+    // An array that had elements has them on lines of their own, so its bracket does too
+    if config.format == OutputFormat::JsonArray {
+        writeln!(printer.out, "{}]", if printer.printed > 0 { "\n" } else { "" })?;
+    }
+
+    if printer.hidden > 0 {
+        writeln!(printer.out, "... and {} more matches", printer.hidden)?;
+    }
+
+    if config.by_extension {
+        printer.print_extensions()?;
+    }
+
+    if config.stats {
+        printer.print_stats(started.elapsed())?;
+    }
+
+    // Like grep, 2 if a file couldn't be read, whatever the rest matched, otherwise 0 when
+    // something was selected, 2 if something was skipped and 1 if nothing was there to select.
+    // For --nth and -L what counts is whether anything was printed, as the match asked for or
+    // a file without any.
+    let found = if config.nth.is_some() || config.files_without_match { printer.printed > 0 } else { printer.stats.matches > 0 };
+    Ok(match found {
+        _ if printer.stats.files_failed > 0 => 2,
+        true => 0,
+        false if skipped > 0 => 2,
+        false => 1,
+    })
+}
+
+/// Printer struct
+///
+/// Writes the output of each file in turn, keeping track of what has already been printed
+/// for the limits and de-duplication that apply across files
+///
+/// # Arguments
+///
+/// * `config` - The given config of the execution
+/// * `out` - Where the lines are written
+/// * `err` - Where warnings about skipped files are written
+/// * `printed` - Number of lines printed so far, for `total_max`
+/// * `hidden` - Number of lines left unprinted once `display_limit` was reached
+/// * `skipped` - Number of lines passed over before the one asked for with `nth`
+/// * `seen` - Matches already printed, for `unique_matches`, or directories for `list_dirs`
+/// * `stats` - Totals of the files printed so far, for `--stats`
+struct Printer<'a> {
+    config: &'a Config,
+    out: &'a mut dyn Write,
+    err: &'a mut dyn Write,
+    printed: usize,
+    hidden: usize,
+    skipped: usize,
+    seen: HashSet<String>,
+    stats: Stats,
+}
+
+impl Printer<'_> {
+    /// Print method
+    ///
+    /// # Parameters
+    ///
+    /// `output` - Output of one file
+    ///
+    /// # Returns
+    ///
+    /// `io::Result<bool>` - False once the --max-total limit has been reached and nothing more will be printed
+    fn print(&mut self, output: FileOutput) -> io::Result<bool> {
+        // With -l -Z every filename is followed by a NUL (including the last) instead of a newline
+        let newline = if self.config.lists_files() && self.config.null { "\0" } else { "\n" };
+        let limit = self.config.total_max.unwrap_or(usize::MAX);
+
+        if self.config.debug {
+            let outcome = if output.timed_out {
+                format!("searched until --per-file-timeout ({} matches)", output.match_count)
+            } else if output.warning.is_some() {
+                "skipped (couldn't be searched)".to_string()
+            } else if output.binary && self.config.binary_mode == BinaryMode::WithoutMatch {
+                "skipped (binary)".to_string()
+            } else if output.binary {
+                format!("searched as binary ({} matches)", output.match_count)
+            } else {
+                format!("searched ({} matches)", output.match_count)
+            };
+            writeln!(self.err, "grep_remake: debug: {}: {}", output.name, outcome)?;
+        }
+        if let Some(warning) = output.warning {
+            writeln!(self.err, "{}", warning)?;
+            // A file that ran out of time was still searched
+            if !output.timed_out {
+                self.stats.files_failed += 1;
+            }
+        }
+        // The offset goes to the error stream so it can be kept without parsing the matches
+        if let Some(offset) = output.end_offset {
+            writeln!(self.err, "grep_remake: {}: end offset {}", output.name, offset)?;
+        }
+
+        self.stats.files_searched += 1;
+        self.stats.matches += output.match_count;
+        if output.match_count > 0 {
+            let extension = Path::new(&output.name).extension().map(|extension| format!(".{}", extension.to_string_lossy()));
+            *self.stats.extension_matches.entry(extension.unwrap_or_else(|| "(none)".to_string())).or_default() += output.match_count;
+            self.stats.file_bytes.push((output.name, output.matched_bytes));
+        }
+
+        let last = output.lines.len().saturating_sub(1);
+        for (index, line) in output.lines.into_iter().enumerate() {
+            let terminator = if output.unterminated && index == last { "" } else { newline };
+            if self.printed >= limit {
+                return Ok(false);
+            }
+
+            // Each distinct match (or directory) is printed once, the first time it is found
+            if (self.config.unique_matches || self.config.list_dirs) && !self.seen.insert(line.clone()) {
+                continue;
+            }
+
+            // With --nth only one line is printed, after which the search stops
+            if let Some(nth) = self.config.nth {
+                // Parallel searches go on emitting after the stop, which must print nothing more
+                if self.printed > 0 {
+                    return Ok(false);
+                }
+                if self.skipped + 1 < nth {
+                    self.skipped += 1;
+                    continue;
+                }
+                self.write_line(&line, terminator)?;
+                return Ok(false);
+            }
+
+            // Past --limit lines are only counted
+            if self.config.display_limit.is_some_and(|display_limit| self.printed >= display_limit) {
+                self.hidden += 1;
+                continue;
+            }
+
+            self.write_line(&line, terminator)?;
+        }
+
+        // Notes are only printed once the lines before them have been
+        if let Some(note) = output.note {
+            writeln!(self.out, "{}", note)?;
+        }
+
+        Ok(self.printed < limit)
+    }
+
+    /// Write line method
+    ///
+    /// # Parameters
+    ///
+    /// `line` - The line to print
+    /// `terminator` - What ends it, usually a newline
+    ///
+    /// # Returns
+    ///
+    /// `io::Result<()>` - Simple error flag
+    fn write_line(&mut self, line: &str, terminator: &str) -> io::Result<()> {
+        // The comma between JSON array elements can only go before the next one, as it isn't
+        // known whether there will be one until it is found
+        if self.config.format == OutputFormat::JsonArray {
+            write!(self.out, "{}{}", if self.printed == 0 { "\n" } else { ",\n" }, line)?;
+        } else {
+            write!(self.out, "{}{}", line, terminator)?;
+        }
+        self.printed += 1;
+        Ok(())
+    }
+
+    /// Print extensions method
+    ///
+    /// # Returns
+    ///
+    /// `io::Result<()>` - Simple error flag
+    fn print_extensions(&mut self) -> io::Result<()> {
+        // The most common extension comes first, ties in name order
+        let mut totals: Vec<(&String, &usize)> = self.stats.extension_matches.iter().collect();
+        totals.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        if totals.is_empty() {
+            return Ok(());
+        }
+
+        let groups: Vec<String> = totals.iter().map(|(extension, count)| format!("{}: {}", extension, count)).collect();
+        writeln!(self.out)?;
+        writeln!(self.out, "{}", groups.join(", "))
+    }
+
+    /// Print stats method
+    ///
+    /// # Parameters
+    ///
+    /// `elapsed` - How long the search took, for the JSON summary
+    ///
+    /// # Returns
+    ///
+    /// `io::Result<()>` - Simple error flag
+    fn print_stats(&mut self, elapsed: Duration) -> io::Result<()> {
+        let stats = &self.stats;
+        let total: usize = stats.file_bytes.iter().map(|(_, bytes)| bytes).sum();
+
+        // A JSON stream stays JSON to the end, so tools can read the matches and summary together
+        if self.config.format == OutputFormat::Json {
+            return writeln!(
+                self.out,
+                "{{\"type\":\"summary\",\"files_searched\":{},\"files_matched\":{},\"matches\":{},\"bytes_matched\":{},\"elapsed_ms\":{}}}",
+                stats.files_searched,
+                stats.file_bytes.len(),
+                stats.matches,
+                total,
+                elapsed.as_millis()
+            );
+        }
+
+        writeln!(self.out)?;
+        for (name, bytes) in &stats.file_bytes {
+            writeln!(self.out, "{}: {} bytes matched", name, bytes)?;
+        }
+        writeln!(
+            self.out,
+            "{} files searched, {} with matches, {} matches, {} bytes matched",
+            stats.files_searched,
+            stats.file_bytes.len(),
+            stats.matches,
+            total
+        )
+    }
+}
+
+/// Collect files method
+///
+/// Builds the list of files to search from the filename arguments, walking directories
+/// when searching recursively. Directory entries are visited in name order so the output
+/// is the same on every run. Without recursion a directory argument is reported on `err`
+/// and skipped, as grep does.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `err` - Where skipped arguments are reported
+///
+/// # Returns
+///
+/// `io::Result<(Vec<Target>, usize)>` - The files to search and the number of arguments skipped
+fn collect_files(config: &Config, err: &mut dyn Write) -> io::Result<(Vec<Target>, usize)> {
+    // A list of files replaces the filename arguments entirely
+    if let Some(list) = &config.files_from {
+        return read_file_list(list, err);
+    }
+
+    let mut files = Vec::new();
+    let mut skipped = 0;
+
+    for filename in &config.filenames {
+        if Path::new(filename).is_dir() {
+            if recurses(config) {
+                let mut walked = Vec::new();
+                walk_dir(Path::new(filename), Path::new(filename), &mut walked)?;
+                // Files named on the command line are searched whatever their type
+                for target in walked {
+                    let skipped_by = if !file_type::selected(&config.types, &[], &target.path) {
+                        Some("--type")
+                    } else if !file_type::selected(&[], &config.types_not, &target.path) {
+                        Some("--type-not")
+                    } else if !path_contains(config, &target.display) {
+                        Some("--path-contains")
+                    } else {
+                        None
+                    };
+                    match skipped_by {
+                        Some(option) if config.debug => writeln!(err, "grep_remake: debug: {}: skipped ({})", target.display, option)?,
+                        Some(_) => {}
+                        None => files.push(target),
+                    }
+                }
+            } else {
+                writeln!(err, "grep_remake: {}: Is a directory", filename)?;
+                skipped += 1;
+            }
+        } else {
+            files.push(Target::new(filename));
+        }
+    }
+
+    Ok((files, skipped))
+}
+
+/// Path contains method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `path` - Path of a walked file, below the directory it was found in
+///
+/// # Returns
+///
+/// `bool` - True when the path contains the `--path-contains` text, or there is none
+fn path_contains(config: &Config, path: &str) -> bool {
+    match &config.path_substring {
+        Some(substring) if config.case_sensitive => path.contains(substring.as_str()),
+        Some(substring) => path.to_lowercase().contains(&substring.to_lowercase()),
+        None => true,
+    }
+}
+
+/// Dedup files method
+///
+/// Folds files with identical contents into the first of them. Files are grouped by their
+/// length and a 64 bit hash, and one is only a duplicate once its bytes are the same as the
+/// first file's, so a hash collision never drops a file. Pipes and other files that aren't
+/// regular are never compared, as reading them here would leave nothing for the search, and
+/// a file that can't be read is reported and kept to be searched on its own.
+///
+/// # Parameters
+///
+/// `files` - The files to search
+/// `err` - Where files that couldn't be compared are reported
+///
+/// # Returns
+///
+/// `io::Result<Vec<Target>>` - The first file with each contents, holding the names of its duplicates
+fn dedup_files(files: Vec<Target>, err: &mut dyn Write) -> io::Result<Vec<Target>> {
+    let mut unique: Vec<Target> = Vec::new();
+    // Indexes into unique of the files seen with each (length, hash), different contents if they collide
+    let mut first_with: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+
+    for target in files {
+        if !fs::metadata(&target.path).is_ok_and(|metadata| metadata.is_file()) {
+            unique.push(target);
+            continue;
+        }
+
+        let contents = match fs::read(&target.path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                writeln!(err, "grep_remake: {}: not compared for --dedup-files: {}", target.display, e)?;
+                unique.push(target);
+                continue;
+            }
+        };
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+
+        let candidates = first_with.entry((contents.len(), hasher.finish())).or_default();
+        // The first file is read again rather than every file being kept in memory
+        let original = candidates.iter().copied().find(|&index| fs::read(&unique[index].path).is_ok_and(|first| first == contents));
+        match original {
+            Some(index) => unique[index].duplicates.push(target.display),
+            None => {
+                candidates.push(unique.len());
+                unique.push(target);
+            }
+        }
+    }
+
+    Ok(unique)
+}
+
+/// Display path method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `path` - A path as it is opened
+///
+/// # Returns
+///
+/// `String` - The path as it is printed, with the `--path-separator` if one was given
+pub(crate) fn display_path(config: &Config, path: &str) -> String {
+    match config.path_separator {
+        Some(separator) => path.replace(['/', '\\'], &separator.to_string()),
+        None => path.to_string(),
+    }
+}
+
+/// Absolute path method
+///
+/// # Parameters
+///
+/// `path` - A path as it is opened
+///
+/// # Returns
+///
+/// `String` - The canonical path, or if that can't be found (say the file has since been
+/// removed) the path joined to the current directory
+fn absolute_path(path: &str) -> String {
+    let absolute = fs::canonicalize(path).or_else(|_| env::current_dir().map(|dir| dir.join(path)));
+    absolute.map(|absolute| absolute.to_string_lossy().into_owned()).unwrap_or_else(|_| path.to_string())
+}
+
+/// Recurses method
+///
+/// Decides whether directories are searched recursively. Without `-r` or `--no-recursive`
+/// this is inferred, recursing when the only path given is a directory.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+///
+/// # Returns
+///
+/// `bool` - True when directories are walked
+fn recurses(config: &Config) -> bool {
+    config.recursive.unwrap_or_else(|| config.filenames.len() == 1 && Path::new(&config.filenames[0]).is_dir())
+}
+
+/// Read file list method
+///
+/// Reads the paths to search from a file, one per line, with `-` reading them from stdin.
+/// Blank lines are ignored and paths that cannot be found are reported on `err` and
+/// skipped rather than stopping the search.
+///
+/// # Parameters
+///
+/// `list` - Path of the file holding the list
+/// `err` - Where missing files are reported
+///
+/// # Returns
+///
+/// `io::Result<(Vec<Target>, usize)>` - The files to search and the number skipped
+fn read_file_list(list: &str, err: &mut dyn Write) -> io::Result<(Vec<Target>, usize)> {
+    let paths = if list == "-" {
+        io::stdin().lock().lines().collect::<io::Result<Vec<_>>>()?
+    } else {
+        fs::read_to_string(list)?.lines().map(String::from).collect()
+    };
+
+    let mut files = Vec::new();
+    let mut skipped = 0;
+
+    for path in paths.into_iter().filter(|path| !path.trim().is_empty()) {
+        match fs::metadata(&path) {
+            Ok(_) => files.push(Target::new(&path)),
+            Err(e) => {
+                writeln!(err, "grep_remake: {}: {}", path, e)?;
+                skipped += 1;
+            }
+        }
+    }
+
+    Ok((files, skipped))
+}
+
+/// Walk directory method
+///
+/// Recursively adds the regular files below a directory. Symbolic links found during the
+/// walk are not followed.
+///
+/// # Parameters
+///
+/// `dir` - The directory to walk
+/// `root` - The directory argument the walk started from, which display names are relative to
+/// `files` - List the files are added to
+///
+/// # Returns
+///
+/// `io::Result<()>` - Simple error flag
+fn walk_dir(dir: &Path, root: &Path, files: &mut Vec<Target>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            walk_dir(&path, root, files)?;
+        } else if file_type.is_file() {
+            // Every walked path starts with its root, so the prefix always strips
+            let display = path.strip_prefix(root).unwrap_or(&path);
+            files.push(Target {
+                path: path.to_string_lossy().into_owned(),
+                display: display.to_string_lossy().into_owned(),
+                duplicates: Vec::new(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Worker count method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+///
+/// # Returns
+///
+/// `usize` - Number of threads to search with, `config.threads` or for 0 the number of CPUs
+fn worker_count(config: &Config) -> usize {
+    match config.threads {
+        0 => thread::available_parallelism().map_or(1, |count| count.get()),
+        threads => threads,
+    }
+}
+
+/// Parallel search method
+///
+/// Searches the files on `config.threads` workers. Workers send each finished file back
+/// over a channel and only the calling thread emits, so a file's lines are always written
+/// together and never interleaved with another file's. By default results are held back
+/// until every earlier file has been emitted, giving the same order as a sequential run;
+/// with `streaming` they are emitted in whatever order the workers finish.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `files` - The files to search
+/// `show_filename` - Whether output lines are prefixed with their file
+/// `emit` - Called with the output of each file
+///
+/// # Returns
+///
+/// `io::Result<()>` - Simple error flag
+fn search_parallel(
+    config: &Config,
+    files: &[Target],
+    show_filename: bool,
+    emit: &mut dyn FnMut(FileOutput) -> io::Result<()>,
+) -> io::Result<()> {
+    // Index of the next file a worker should pick up
+    let next_file = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..worker_count(config).min(files.len()) {
+            let sender = sender.clone();
+            let next_file = &next_file;
+
+            scope.spawn(move || loop {
+                // Claim the next unsearched file, stopping when there are none left
+                let index = next_file.fetch_add(1, Ordering::SeqCst);
+                let target = match files.get(index) {
+                    Some(target) => target,
+                    None => break,
+                };
+
+                // Stop early if the emitting side has given up (after an error)
+                if sender.send((index, search_file(config, target, show_filename))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // Only the workers hold senders now, so the loop below ends once they finish
+        drop(sender);
+
+        // Finished files waiting for an earlier file to be emitted first
+        let mut pending: Vec<Option<FileOutput>> = (0..files.len()).map(|_| None).collect();
+        let mut next_to_print = 0;
+
+        for (index, result) in receiver {
+            let output = result?;
+
+            if config.streaming {
+                // Emit straight away, accepting whichever order the files finished in
+                emit(output)?;
+                continue;
+            }
+
+            // Emit every file that is now next in argument order
+            pending[index] = Some(output);
+            while let Some(output) = pending.get_mut(next_to_print).and_then(Option::take) {
+                emit(output)?;
+                next_to_print += 1;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Search file method
+///
+/// Searches a single file, producing the lines to output. A file that can't be opened or
+/// read, such as a missing one or one that isn't UTF-8, only skips that file with a warning,
+/// so the files after it are still searched and the exit status is 2 if nothing matched.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `target` - The file to search
+/// `show_filename` - Whether output lines are prefixed with the filename
+///
+/// # Returns
+///
+/// `io::Result<FileOutput>` - Output lines, or just the filename with -l, and an error only
+/// when Ctrl-C stopped the search
+fn search_file(config: &Config, target: &Target, show_filename: bool) -> io::Result<FileOutput> {
+    match search_target(config, target, show_filename) {
+        Err(e) if e.kind() != io::ErrorKind::Interrupted => {
+            let warning = Some(format!("grep_remake: {}: {}", target.display, e));
+            Ok(FileOutput { warning, name: target.display.clone(), ..FileOutput::default() })
+        }
+        searched => searched,
+    }
+}
+
+/// Search target method
+///
+/// Does the work of `search_file`, returning an error for a file that can't be searched
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `target` - The file to search
+/// `show_filename` - Whether output lines are prefixed with the filename
+///
+/// # Returns
+///
+/// `io::Result<FileOutput>` - Output lines, or just the filename with -l
+fn search_target(config: &Config, target: &Target, show_filename: bool) -> io::Result<FileOutput> {
+    let (filename, display) = (target.path.as_str(), target.display.as_str());
+
+    // Files matching --ignore-case-files are searched with their own, case insensitive, config
+    let insensitive;
+    let config = if config.case_sensitive && config.case_insensitive_globs.iter().any(|glob| glob::matches_path(glob, filename)) {
+        insensitive = Config { case_sensitive: false, ..config.clone() };
+        &insensitive
+    } else {
+        config
+    };
+    let name = if show_filename || config.first_match_per_file { Some(display.to_string()) } else { None };
+
+    // Where a plain file's search stopped, for --resume-from
+    let mut end_offset = None;
+    // Archive members that were skipped, each reported after the file's output
+    let mut skipped_members = Vec::new();
+
+    // Each source of lines, with the name its output is prefixed with
+    let sources = if let Some(command) = &config.preprocessor {
+        match preprocess(config, command, filename) {
+            Ok(scan) => vec![(name, scan)],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+            // A failing converter only skips its file
+            Err(e) => {
+                let warning = Some(format!("grep_remake: {}: preprocessor failed: {}", display, e));
+                return Ok(FileOutput { warning, name: display.to_string(), ..FileOutput::default() });
+            }
+        }
+    } else if config.archives && archive::is_archive(filename) {
+        // Archives are searched member by member
+        match archive::search_archive(config, filename, display) {
+            Ok((sources, warnings)) => {
+                skipped_members = warnings;
+                sources
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+            // A corrupt archive only skips that file
+            Err(e) => {
+                let warning = Some(format!("grep_remake: {}: {}", display, e));
+                return Ok(FileOutput { warning, name: display.to_string(), ..FileOutput::default() });
+            }
+        }
+    } else if let Some(decompressor) = decompress::decompressor_for(filename).filter(|_| config.decompress) {
+        match decompress::search_compressed(config, decompressor, filename) {
+            Ok(scan) => vec![(name, scan)],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+            // A file that won't decompress only skips that file
+            Err(e) => {
+                let warning = Some(format!("grep_remake: {}: {}", display, e));
+                return Ok(FileOutput { warning, name: display.to_string(), ..FileOutput::default() });
+            }
+        }
+    } else {
+        // Streams the file rather than reading it all into memory, which also suits pipes
+        let mut file = fs::File::open(filename)?;
+        let metadata = file.metadata()?;
+        let regular = metadata.is_file();
+        if let Some(offset) = config.resume_from {
+            if regular {
+                file.seek(SeekFrom::Start(offset))?;
+            } else {
+                // Pipes and devices can't seek, so the bytes before the offset are read past
+                io::copy(&mut (&mut file).take(offset), &mut io::sink())?;
+            }
+        }
+        let mut reader = BufReader::new(file);
+
+        // Like grep, a NUL byte in the first block read marks the file as binary
+        let binary = config.binary_mode != BinaryMode::Text && reader.fill_buf()?.contains(&0);
+        if binary && config.binary_mode == BinaryMode::WithoutMatch {
+            return Ok(FileOutput { name: display.to_string(), binary, ..FileOutput::default() });
+        }
+
+        // A big enough file can be split up and searched on several threads
+        let start = config.resume_from.unwrap_or(0);
+        let chunk_size = config.chunk_size.filter(|size| {
+            regular && !binary && worker_count(config) > 1 && chunked::chunkable(config) && metadata.len().saturating_sub(start) > *size
+        });
+
+        // Binary files often aren't UTF-8, and their lines aren't printed, so they are always read lossily
+        let scan = if binary && !config.lossy_utf8 {
+            search_stream(&Config { lossy_utf8: true, ..config.clone() }, reader)?
+        } else if let Some(size) = chunk_size {
+            chunked::search_chunked(config, filename, start, size, worker_count(config))?
+        } else {
+            search_stream(config, reader)?
+        };
+        end_offset = config.resume_from.map(|offset| offset + scan.bytes_read);
+
+        // Binary lines would be garbage on a terminal, so only the fact that it matched is printed
+        let prints_lines = !(config.lists_files() || config.count || config.denominator.is_some());
+        if binary && prints_lines && !scan.matches.is_empty() {
+            let notice = if config.binary_count {
+                // Every occurrence counts, as a binary file's "lines" can be any length
+                let total = if config.invert_match {
+                    scan.matches.len()
+                } else {
+                    scan.matches.iter().map(|(_, line)| find_patterns(config, line).len()).sum()
+                };
+                format!("Binary file {}: {} {}", display, total, if total == 1 { "match" } else { "matches" })
+            } else {
+                format!("Binary file {} matches", display)
+            };
+            return Ok(FileOutput {
+                lines: vec![notice],
+                name: display.to_string(),
+                match_count: count_matches(config, &scan.matches),
+                end_offset,
+                binary,
+                warning: timeout_warning(config, display, scan.timed_out),
+                timed_out: scan.timed_out,
+                ..FileOutput::default()
+            });
+        }
+
+        vec![(name, scan)]
+    };
+
+    let match_count = sources.iter().map(|(_, scan)| count_matches(config, &scan.matches)).sum();
+    let timed_out = sources.iter().any(|(_, scan)| scan.timed_out);
+    let warning = skipped_members.into_iter().chain(timeout_warning(config, display, timed_out)).reduce(|all, warning| all + "\n" + &warning);
+    // Bytes of every matching line, for the modes that print something other than the lines
+    let line_bytes = sources.iter().flat_map(|(_, scan)| &scan.matches).map(|(_, line)| line.len()).sum();
+
+    // -l only reports which files matched, and -L which didn't
+    if config.lists_files() {
+        // Duplicates match exactly when their original does
+        let lines = if (match_count > 0) != config.files_without_match {
+            let listed = std::iter::once(display).chain(target.duplicates.iter().map(String::as_str));
+            // Directories are printed once across all files by the printer
+            if config.list_dirs {
+                listed.map(parent_dir).collect()
+            } else {
+                listed.map(String::from).collect()
+            }
+        } else {
+            Vec::new()
+        };
+        let name = display.to_string();
+        return Ok(FileOutput { lines, warning, match_count, name, matched_bytes: line_bytes, end_offset, timed_out, ..FileOutput::default() });
+    }
+
+    let mut lines = Vec::new();
+    let mut note = None;
+    // Matches taken so far across all sources, for --max-per-file
+    let mut matched = 0;
+    let mut matched_bytes = 0;
+    let mut truncated = false;
+
+    // Blame is run once for the whole file, and only if something in it will be printed
+    let plain_file = config.preprocessor.is_none() && !(config.archives && archive::is_archive(filename));
+    let blame = if config.blame && plain_file && match_count > 0 { blame::blame(filename) } else { None };
+
+    // Length of lines when the unterminated last line of a file was added, if it was
+    let mut unterminated_at = None;
+
+    for (name, mut scan) in sources {
+        let prefix = name.as_deref();
+        // Only plain text can be left without a newline, CSV and JSON rows always have one
+        let unterminated_line = scan.unterminated_line.filter(|_| {
+            config.no_trailing_newline && config.format == OutputFormat::Text && !config.unique_matches
+        });
+
+        if config.count || config.denominator.is_some() {
+            let total = if config.count_bytes {
+                count_bytes(config, &scan.matches)
+            } else if config.count_distinct {
+                count_distinct(config, &scan.matches)
+            } else {
+                count_matches(config, &scan.matches)
+            };
+            let values = match config.denominator {
+                // Every source has a density, even one with no matches
+                Some(_) => vec![format!("{}/{}", total, scan.denominator)],
+                // Sources without matches are left out of the counts, unless --with-zero
+                None if total == 0 && !config.with_zero => continue,
+                None if config.span => vec![span_summary(&scan.matches, total)],
+                None if config.count_per_pattern => pattern_counts(config, &scan.matches),
+                None => vec![total.to_string()],
+            };
+            // A span is several fields, so a space keeps it apart from the name
+            let separator = match (config.null, config.span) {
+                (true, _) => "\0",
+                (false, true) => ": ",
+                (false, false) => ":",
+            };
+            for value in values {
+                lines.push(match prefix {
+                    // CSV always has the file column, whether or not text output would show it
+                    _ if config.format == OutputFormat::Csv => csv_row(&[prefix.unwrap_or(display), &value]),
+                    Some(name) => format!("{}{}{}", name, separator, value),
+                    None => value,
+                });
+            }
+            continue;
+        }
+
+        // Sorting is stable, so lines starting with the same number stay in file order
+        if config.sort_numeric {
+            if config.non_numeric_first {
+                scan.matches.sort_by_key(|(_, line)| leading_number(line));
+            } else {
+                scan.matches.sort_by_key(|(_, line)| {
+                    let number = leading_number(line);
+                    (number.is_none(), number)
+                });
+            }
+        }
+
+        // Context is only printed around whole lines, in file order
+        let per_match = config.only_matching || config.unique_matches || config.template.is_some();
+        let wants_context = config.before_context > 0 || config.after_context > 0 || config.paragraph_mode;
+        let show_context = wants_context && !per_match && !config.sort_numeric && config.nth.is_none() && config.format == OutputFormat::Text;
+        let mut context = scan.context.into_iter().peekable();
+        // Number of the last line printed from this source, for the `--` between context groups
+        let mut last_printed = None;
+        // The run of identical lines being collapsed with --collapse
+        let mut repeat: Option<Repeat> = None;
+        // Headers in line order, and the one the current match comes after
+        let mut sections = scan.sections.into_iter().peekable();
+        let mut section = None;
+
+        for (line_number, line) in scan.matches {
+            // With --max-per-file the rest of the file's matches are dropped
+            if config.per_file_max.is_some_and(|max| matched >= max) {
+                truncated = true;
+                break;
+            }
+
+            while let Some((number, text)) = context.next_if(|(number, _)| *number < line_number) {
+                if show_context {
+                    separate_group(config, &mut lines, &mut last_printed, number);
+                    lines.push(format_line(config, prefix, number, text, LineKind::Context, None));
+                }
+            }
+            // A header on the matched line itself heads the lines after it, not the line
+            while let Some((_, text)) = sections.next_if(|(number, _)| *number < line_number) {
+                section = Some(text);
+            }
+
+            // Every match is printed on its own line with -o, and a template is expanded for each
+            let found = if per_match {
+                let mut found = find_patterns(config, &line);
+                // Only the output is capped, counts were taken from the whole line
+                if let Some(max) = config.per_line_max {
+                    found.truncate(max);
+                }
+                found
+            } else {
+                let whole = 0..line.len();
+                vec![whole]
+            };
+
+            for range in found {
+                // With -o the limit can be reached part way through a line
+                if config.per_file_max.is_some_and(|max| matched >= max) {
+                    truncated = true;
+                    break;
+                }
+                matched += 1;
+                matched_bytes += range.len();
+
+                // A line the same as the one matched just before it only adds to that line's count
+                if config.collapse && !per_match {
+                    if let Some(run) = repeat.as_mut().filter(|run| run.continues(line_number, &line, lines.len())) {
+                        run.count += 1;
+                        run.line_number = line_number;
+                        continue;
+                    }
+                    finish_repeat(&mut lines, repeat.take());
+                }
+
+                if let Some(template) = &config.template {
+                    lines.push(template.expand(prefix.unwrap_or(display), line_number, &line, range));
+                    continue;
+                }
+
+                if show_context {
+                    separate_group(config, &mut lines, &mut last_printed, line_number);
+                }
+
+                let text = line[range].to_string();
+                lines.push(match config.format {
+                    // Unique matches are compared and printed without any prefix
+                    _ if config.unique_matches => text,
+                    OutputFormat::Text => {
+                        let formatted = format_line(config, prefix, line_number, text, LineKind::Match, section.as_deref());
+                        match blame.as_ref().and_then(|blame| blame.get(&line_number)) {
+                            Some(annotation) => format!("{} {}", formatted, annotation),
+                            None => formatted,
+                        }
+                    }
+                    // CSV always has the file column, whether or not text output would show it
+                    OutputFormat::Csv => {
+                        let text = display_text(config, text);
+                        csv_row(&[prefix.unwrap_or(display), &line_number.to_string(), &text])
+                    }
+                    OutputFormat::Json | OutputFormat::JsonArray => {
+                        let text = display_text(config, text);
+                        json_row(prefix.unwrap_or(display), line_number, &text, &find_printed(config, &text))
+                    }
+                    OutputFormat::Markdown => {
+                        let text = display_text(config, text);
+                        markdown_row(prefix.unwrap_or(display), line_number, &text, config.max_columns)
+                    }
+                });
+                if unterminated_line == Some(line_number) {
+                    unterminated_at = Some(lines.len());
+                }
+                if config.collapse && !per_match {
+                    repeat = Some(Repeat { text: line.clone(), line_number, count: 1, index: lines.len() - 1 });
+                }
+            }
+        }
+        finish_repeat(&mut lines, repeat.take());
+
+        if truncated {
+            if config.truncation_note {
+                note = Some(match prefix {
+                    Some(name) => format!("{}{}... (truncated)", name, if config.null { '\0' } else { ':' }),
+                    None => "... (truncated)".to_string(),
+                });
+            }
+            break;
+        }
+
+        // Context after the last match
+        if show_context {
+            for (number, text) in context {
+                separate_group(config, &mut lines, &mut last_printed, number);
+                lines.push(format_line(config, prefix, number, text, LineKind::Context, None));
+                if unterminated_line == Some(number) {
+                    unterminated_at = Some(lines.len());
+                }
+            }
+        }
+
+        // Every paragraph is followed by a blank line, the last included
+        if config.paragraph_mode && show_context && last_printed.is_some() {
+            lines.push(String::new());
+        }
+    }
+
+    // Counts print no text, so their bytes are those of the lines counted
+    if config.count || config.denominator.is_some() {
+        matched_bytes = line_bytes;
+    }
+
+    // Duplicates have the same matches, so they point to these rather than repeat them
+    if match_count > 0 {
+        lines.extend(target.duplicates.iter().map(|duplicate| format!("{}: duplicate of {}", duplicate, display)));
+    }
+
+    // Nothing may have been added after the unterminated line, not even a duplicate
+    let unterminated = unterminated_at == Some(lines.len());
+    let name = display.to_string();
+    Ok(FileOutput { lines, note, warning, name, match_count, matched_bytes, end_offset, unterminated, binary: false, timed_out })
+}
+
+/// Repeat struct
+///
+/// A run of identical matching lines being collapsed into one with `--collapse`
+///
+/// # Arguments
+///
+/// * `text` - Text of the lines
+/// * `line_number` - Number of the last line in the run
+/// * `count` - Number of lines in the run
+/// * `index` - Position of the run's printed line in the output
+struct Repeat {
+    text: String,
+    line_number: usize,
+    count: usize,
+    index: usize,
+}
+
+impl Repeat {
+    /// Continues method
+    ///
+    /// # Parameters
+    ///
+    /// `line_number` - Number of a matching line
+    /// `text` - Its text
+    /// `printed` - Number of lines output so far
+    ///
+    /// # Returns
+    ///
+    /// `bool` - True when the line is the same as the run's and straight after it, with nothing printed since
+    fn continues(&self, line_number: usize, text: &str, printed: usize) -> bool {
+        self.line_number + 1 == line_number && self.text == text && self.index + 1 == printed
+    }
+}
+
+/// Finish repeat method
+///
+/// # Parameters
+///
+/// `lines` - Lines output so far
+/// `repeat` - The run that has ended, if any, whose line is given its count once there was more than one
+fn finish_repeat(lines: &mut [String], repeat: Option<Repeat>) {
+    if let Some(run) = repeat.filter(|run| run.count > 1) {
+        lines[run.index].push_str(&format!(" (repeated {} times)", run.count));
+    }
+}
+
+/// Preprocess method
+///
+/// Runs the preprocessor on a file and searches what it writes to stdout, as it is written
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `command` - The preprocessor command, the path being added as its last argument
+/// `filename` - The file to convert
+///
+/// # Returns
+///
+/// `io::Result<Scan>` - Matches in the converted text, or an error if the command could not
+/// be run or did not exit successfully
+fn preprocess(config: &Config, command: &str, filename: &str) -> io::Result<Scan> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+
+    let mut child = Command::new(program)
+        .args(words)
+        .arg(filename)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("preprocessor stdout is piped");
+
+    let scan = search_stream(config, BufReader::new(stdout));
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!("{} exited with {}", program, status)));
+    }
+    scan
+}
+
+/// Count matches method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `results` - Matching lines of one source
+///
+/// # Returns
+///
+/// `usize` - The number of matching lines, or with -o the number of matches in them
+fn count_matches(config: &Config, results: &[(usize, String)]) -> usize {
+    if config.only_matching {
+        results.iter().map(|(_, line)| find_patterns(config, line).len()).sum()
+    } else {
+        results.len()
+    }
+}
+
+/// Timeout warning method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `display` - Name of the file
+/// `timed_out` - Whether its search ran past `--per-file-timeout`
+///
+/// # Returns
+///
+/// `Option<String>` - The warning that the rest of the file wasn't searched, if it wasn't
+fn timeout_warning(config: &Config, display: &str, timed_out: bool) -> Option<String> {
+    let budget = config.per_file_timeout.filter(|_| timed_out)?;
+    Some(format!("grep_remake: {}: stopped after {}ms, the rest of it wasn't searched", display, budget.as_millis()))
+}
+
+/// Leading number method
+///
+/// # Parameters
+///
+/// `line` - Text of a line, which may start with spaces before its number
+///
+/// # Returns
+///
+/// `Option<i64>` - The integer the line starts with, which can be negative, or None if it
+/// doesn't start with one or it is too big to compare
+fn leading_number(line: &str) -> Option<i64> {
+    let text = line.trim_start();
+    let sign = usize::from(text.starts_with('-') || text.starts_with('+'));
+    let digits = text[sign..].bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    text[..sign + digits].parse().ok()
+}
+
+/// Span summary method
+///
+/// # Parameters
+///
+/// `results` - Matching lines of one source, in order
+/// `total` - How many matches they were counted as
+///
+/// # Returns
+///
+/// `String` - The first and last matching line numbers and the count, such as
+/// `first=12 last=340 count=9`, or `none` with no matches
+fn span_summary(results: &[(usize, String)], total: usize) -> String {
+    match (results.first(), results.last()) {
+        (Some((first, _)), Some((last, _))) => format!("first={} last={} count={}", first, last, total),
+        _ => "none".to_string(),
+    }
+}
+
+/// Count bytes method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `results` - Matching lines of one source
+///
+/// # Returns
+///
+/// `usize` - The number of bytes in the matching lines, or with -o in the matches
+fn count_bytes(config: &Config, results: &[(usize, String)]) -> usize {
+    if config.only_matching {
+        results.iter().flat_map(|(_, line)| find_patterns(config, line)).map(|range| range.len()).sum()
+    } else {
+        results.iter().map(|(_, line)| line.len()).sum()
+    }
+}
+
+/// Pattern counts method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `results` - Matching lines of one source
+///
+/// # Returns
+///
+/// `Vec<String>` - `pattern: count` for each pattern in the order given, counting the lines
+/// it is in
+fn pattern_counts(config: &Config, results: &[(usize, String)]) -> Vec<String> {
+    // Every pattern is looked for in every line, rather than stopping at the first found
+    config
+        .patterns()
+        .into_iter()
+        .map(|pattern| {
+            let lines = results.iter().filter(|(_, line)| !find_all(pattern, line, config.case_sensitive).is_empty()).count();
+            format!("{}: {}", pattern, lines)
+        })
+        .collect()
+}
+
+/// Count distinct method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `results` - Matching lines of one source
+///
+/// # Returns
+///
+/// `usize` - The number of different matching lines, or with -o of different matches
+fn count_distinct(config: &Config, results: &[(usize, String)]) -> usize {
+    // Only one source's matches are held at a time
+    let mut distinct = HashSet::new();
+    for (_, line) in results {
+        if config.only_matching {
+            distinct.extend(find_patterns(config, line).into_iter().map(|range| &line[range]));
+        } else {
+            distinct.insert(line.as_str());
+        }
+    }
+    distinct.len()
+}
+
+/// Separate group method
+///
+/// Adds the group separator (`--` unless changed) between groups of context that aren't
+/// next to each other. Paragraphs are separated by a blank line instead.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `lines` - Lines printed so far
+/// `last_printed` - Number of the last line printed, updated to `line_number`
+/// `line_number` - Number of the line about to be printed
+fn separate_group(config: &Config, lines: &mut Vec<String>, last_printed: &mut Option<usize>, line_number: usize) {
+    if last_printed.is_some_and(|last| last + 1 < line_number) {
+        lines.push(if config.paragraph_mode { String::new() } else { config.group_separator.clone() });
+    }
+    *last_printed = Some(line_number);
+}
+
+/// Format line method
+///
+/// Builds the output line for a match or context line, adding the filename and line number
+/// prefixes through a `FormattedMatch`
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `filename` - Name to prefix the line with, if any
+/// `line_number` - Position of the line in its file, starting at 1
+/// `line` - Text of the line
+/// `kind` - Whether the line matched or is context around a match
+/// `section` - The section header the line comes after, for `--section`
+///
+/// # Returns
+///
+/// `String` - The line to print
+pub(crate) fn format_line(
+    config: &Config,
+    filename: Option<&str>,
+    line_number: usize,
+    line: String,
+    kind: LineKind,
+    section: Option<&str>,
+) -> String {
+    let mut output = String::new();
+    if config.mark_matches {
+        output.push_str(if kind == LineKind::Match { "> " } else { "  " });
+    }
+
+    // Sections, labels and occurrence counts come between the prefixes and the line itself
+    let mut text = String::new();
+    if let Some(section) = section {
+        text.push_str(section);
+        text.push(' ');
+    }
+    if config.label_patterns && kind == LineKind::Match {
+        let labels: Vec<&str> = config
+            .patterns()
+            .into_iter()
+            .filter(|pattern| !find_all(pattern, &line, config.case_sensitive).is_empty())
+            .collect();
+        if !labels.is_empty() {
+            text.push_str(&format!("[{}] ", labels.join(",")));
+        }
+    }
+
+    if config.show_line_occurrences && kind == LineKind::Match {
+        let occurrences = find_printed(config, &line).len();
+        if occurrences != 1 || !config.omit_single_occurrence {
+            text.push_str(&format!("(x{}): ", occurrences));
+        }
+    }
+
+    if let Some(format) = config.timestamp_format.as_ref().filter(|_| kind == LineKind::Match) {
+        if let Some(then) = timestamp::parse_prefix(format, &line) {
+            text.push_str(&format!("[{}] ", timestamp::relative(then, timestamp::now())));
+        }
+    }
+
+    let decoration = text.len();
+    text.push_str(&display_text(config, line));
+
+    // Matches are found again in the printed text, as expanding tabs moves them
+    let mut highlights: Vec<Range<usize>> = if config.color && kind == LineKind::Match {
+        find_printed(config, &text[decoration..]).into_iter().map(|range| range.start + decoration..range.end + decoration).collect()
+    } else {
+        Vec::new()
+    };
+
+    // With --max-columns the line is cut to fit, its prefixes included, and so are its highlights
+    if let Some(max) = config.max_columns.filter(|max| *max > 0) {
+        let prefixes = output.chars().count()
+            + filename.map_or(0, |name| name.chars().count() + 1)
+            + if config.line_number { line_number.to_string().len() + 1 } else { 0 };
+        if let Some(cut) = column_cut(&text, max.saturating_sub(prefixes)) {
+            text.truncate(cut);
+            text.push('…');
+            highlights.retain(|range| range.start < cut);
+            for range in &mut highlights {
+                range.end = range.end.min(cut);
+            }
+        }
+    }
+
+    let formatted = FormattedMatch {
+        path: filename.unwrap_or_default(),
+        line_number,
+        text: &text,
+        highlights,
+        context: kind == LineKind::Context,
+        show_filename: filename.is_some(),
+        show_line_number: config.line_number,
+        null: config.null,
+        color: config.color,
+        context_style: config.context_style,
+    };
+    output.push_str(&formatted.to_string());
+    output
+}
+
+/// Parent dir method
+///
+/// # Parameters
+///
+/// `path` - Path of a file, as it is printed
+///
+/// # Returns
+///
+/// `String` - The directory the file is in, `.` for a file named without one
+fn parent_dir(path: &str) -> String {
+    match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    }
+}
+
+/// Column cut method
+///
+/// # Parameters
+///
+/// `text` - Text to fit
+/// `max` - Most characters that can be shown
+///
+/// # Returns
+///
+/// `Option<usize>` - Byte offset to cut the text at so that, with a `…` added, it is `max`
+/// characters long, or None if the text fits already
+fn column_cut(text: &str, max: usize) -> Option<usize> {
+    if text.chars().count() <= max {
+        return None;
+    }
+    // The ellipsis takes the last column
+    Some(text.char_indices().nth(max.saturating_sub(1)).map_or(text.len(), |(index, _)| index))
+}
+
+/// Match windows method
+///
+/// Cuts a line down to the text around its matches, for long lines such as minified files
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+/// `matches` - Byte ranges of the matches in the line, in order
+/// `context` - Characters to keep on each side of a match
+///
+/// # Returns
+///
+/// `String` - The windows, joined where they overlap, or the whole line if it has no matches
+fn match_windows(line: &str, matches: &[Range<usize>], context: usize) -> String {
+    let windows = matches.iter().map(|found| {
+        // Stepping back and forward by characters, so a window never splits one
+        let start = match context {
+            0 => found.start,
+            _ => line[..found.start].char_indices().rev().nth(context - 1).map_or(0, |(index, _)| index),
+        };
+        let end = line[found.end..].char_indices().nth(context).map_or(line.len(), |(index, _)| found.end + index);
+        start..end
+    });
+    join_windows(line, windows.collect())
+}
+
+/// Balanced spans method
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+/// `matches` - Byte ranges of the matches in the line
+/// `pairs` - Opening and closing brackets
+///
+/// # Returns
+///
+/// `String` - The nearest balanced brackets around each match, with a `…` wherever text was
+/// left out. A match outside any pair keeps the whole line.
+fn balanced_spans(line: &str, matches: &[Range<usize>], pairs: &[(char, char)]) -> String {
+    let spans = matches.iter().map(|found| balance::enclosing(line, found, pairs).unwrap_or(0..line.len()));
+    join_windows(line, spans.collect())
+}
+
+/// Join windows method
+///
+/// # Parameters
+///
+/// `line` - Text of the line
+/// `windows` - Byte ranges of the parts of the line to print, which may overlap
+///
+/// # Returns
+///
+/// `String` - The parts in order, those that touch or overlap joined into one, with a `…`
+/// wherever text was left out, or the whole line if there are none
+fn join_windows(line: &str, mut windows: Vec<Range<usize>>) -> String {
+    windows.sort_by_key(|window| window.start);
+    let mut joined: Vec<Range<usize>> = Vec::new();
+    for window in windows {
+        match joined.last_mut() {
+            Some(last) if window.start <= last.end => last.end = last.end.max(window.end),
+            _ => joined.push(window),
+        }
+    }
+    let windows = joined;
+
+    let (first, last) = match (windows.first(), windows.last()) {
+        (Some(first), Some(last)) => (first.start, last.end),
+        _ => return line.to_string(),
+    };
+    let mut text = if first > 0 { "…".to_string() } else { String::new() };
+    text.push_str(&windows.iter().map(|window| &line[window.clone()]).collect::<Vec<_>>().join("…"));
+    if last < line.len() {
+        text.push('…');
+    }
+    text
+}
+
+/// CSV row method
+///
+/// # Parameters
+///
+/// `fields` - Values of the row's columns
+///
+/// # Returns
+///
+/// `String` - The fields joined by commas, each quoted if it contains a comma, quote or line break
+fn csv_row(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                // Quotes inside a quoted field are doubled
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Markdown row method
+///
+/// # Parameters
+///
+/// `path` - Name of the file
+/// `line_number` - Position of the line in its file, starting at 1
+/// `text` - Text of the line
+/// `max_columns` - Longest the text can be in characters before it is cut off with `…`
+///
+/// # Returns
+///
+/// `String` - The line as a Markdown table row, such as `| a.txt | 3 | a \| b |`
+fn markdown_row(path: &str, line_number: usize, text: &str, max_columns: Option<usize>) -> String {
+    // Cut before escaping so an escape is never split
+    let text = match max_columns.filter(|max| *max > 0).and_then(|max| column_cut(text, max)) {
+        Some(cut) => format!("{}…", &text[..cut]),
+        None => text.to_string(),
+    };
+    // A pipe would end the cell, so it is backslash escaped
+    let escape = |cell: &str| cell.replace('|', "\\|");
+    format!("| {} | {} | {} |", escape(path), line_number, escape(&text))
+}
+
+/// JSON row method
+///
+/// # Parameters
+///
+/// `path` - Name of the file
+/// `line_number` - Position of the line in its file, starting at 1
+/// `text` - Text of the line
+/// `matches` - Byte ranges of the matches within the text, in order and not overlapping
+///
+/// # Returns
+///
+/// `String` - The line as a JSON object on one line, such as
+/// `{"path":"a.txt","line_number":3,"text":"a b a","matches":[{"start":0,"end":1},{"start":4,"end":5}]}`
+fn json_row(path: &str, line_number: usize, text: &str, matches: &[Range<usize>]) -> String {
+    let matches: Vec<String> =
+        matches.iter().map(|range| format!("{{\"start\":{},\"end\":{}}}", range.start, range.end)).collect();
+    format!(
+        "{{\"path\":{},\"line_number\":{},\"text\":{},\"matches\":[{}]}}",
+        json_string(path),
+        line_number,
+        json_string(text),
+        matches.join(",")
+    )
+}
+
+/// JSON string method
+///
+/// # Parameters
+///
+/// `text` - Text to quote
+///
+/// # Returns
+///
+/// `String` - The text as a JSON string, with quotes, backslashes and control characters escaped
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if u32::from(c) < 0x20 => quoted.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Display text method
+///
+/// Applies the display-only changes to a matched line. Matching has already happened on
+/// the original text, so these never affect what matches.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `line` - Text of the matched line
+///
+/// # Returns
+///
+/// `String` - The text to print
+fn display_text(config: &Config, line: String) -> String {
+    let line = match config.tab_width {
+        Some(width) => expand_tabs(&line, width),
+        None => line,
+    };
+    // Trimmed after expanding, so tabs inside the line still line up with the original
+    let line = if config.trim && line.trim().len() != line.len() { line.trim().to_string() } else { line };
+
+    if let Some(pairs) = &config.balance {
+        return balanced_spans(&line, &find_printed(config, &line), pairs);
+    }
+    let line = match config.match_context {
+        Some(context) => match_windows(&line, &find_printed(config, &line), context),
+        None => line,
+    };
+
+    // Replaced last, as the rest need to find the matches
+    match replace_matches(config, &line) {
+        Cow::Owned(replaced) => replaced,
+        Cow::Borrowed(_) => line,
+    }
+}
+
+/// Replace matches method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `line` - Text of the line
+///
+/// # Returns
+///
+/// `Cow<str>` - The line with each match replaced by `config.replace`, borrowed when there
+/// is no replacement or nothing to replace
+pub(crate) fn replace_matches<'a>(config: &Config, line: &'a str) -> Cow<'a, str> {
+    let replacement = match &config.replace {
+        Some(replacement) => replacement,
+        None => return Cow::Borrowed(line),
+    };
+    let found = find_printed(config, line);
+    if found.is_empty() {
+        return Cow::Borrowed(line);
+    }
+
+    let mut replaced = String::with_capacity(line.len());
+    let mut end = 0;
+    for range in found {
+        replaced.push_str(&line[end..range.start]);
+        replaced.push_str(replacement);
+        end = range.end;
+    }
+    replaced.push_str(&line[end..]);
+    Cow::Owned(replaced)
+}
+
+/// Expand tabs method
+///
+/// Replaces each tab with the spaces needed to reach the next tab stop, so text after
+/// the tab lines up whatever came before it on the line
+///
+/// # Parameters
+///
+/// `line` - Text to expand
+/// `width` - Distance between tab stops
+///
+/// # Returns
+///
+/// `String` - The text without tabs
+pub fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    // Column of the next character, counted in characters
+    let mut column = 0;
+
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+
+    expanded
+}
+
+/// Lines searched between looks at the clock for `--per-file-timeout`
+const TIMEOUT_CHECK_LINES: usize = 256;
+
+/// Over budget method
+///
+/// # Parameters
+///
+/// `budget` - Time the search of a file may take, if limited
+/// `line_number` - Number of lines searched so far
+/// `started` - When the search started
+///
+/// # Returns
+///
+/// `bool` - True when the search should stop. The clock is only read every
+/// `TIMEOUT_CHECK_LINES` lines, so short files always finish.
+fn over_budget(budget: Option<Duration>, line_number: usize, started: Instant) -> bool {
+    budget.is_some_and(|budget| line_number > 0 && line_number.is_multiple_of(TIMEOUT_CHECK_LINES) && started.elapsed() >= budget)
+}
+
+/// Search stream method
+///
+/// Reads lines one at a time from a reader, keeping only the lines that match
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `reader` - Source of the lines to search
+///
+/// # Returns
+///
+/// `io::Result<Scan>` - The matching lines, and how many lines contain the denominator pattern
+pub(crate) fn search_stream<R: BufRead>(config: &Config, mut reader: R) -> io::Result<Scan> {
+    // Case insensitive patterns are compiled once, and lowercase each line into a buffer they keep
+    let patterns: Vec<&str> = config.patterns();
+    let compiled: Vec<CompiledQuery> =
+        if config.case_sensitive { Vec::new() } else { patterns.iter().map(|pattern| CompiledQuery::new(pattern)).collect() };
+    let denominator = config.denominator.as_ref().map(|pattern| fold_case(config, pattern).into_owned());
+    let range_start = config.range_start.as_ref().map(|pattern| fold_case(config, pattern).into_owned());
+    let range_end = config.range_end.as_ref().map(|pattern| fold_case(config, pattern).into_owned());
+    let section_pattern = config.section_pattern.as_ref().map(|pattern| fold_case(config, pattern).into_owned());
+    let in_code_regions = config.in_comments || config.in_strings;
+
+    // Without a start pattern the range is open from the first line
+    let range_mode = range_start.is_some() || range_end.is_some();
+    let mut in_range = range_start.is_none();
+    // The patterns other than the query are looked for in the whole line case folded, which is
+    // only made when one of them is given
+    let folds_lines = range_mode || denominator.is_some() || section_pattern.is_some();
+
+    // Buffer reused for every line, read as bytes so invalid UTF-8 can be replaced with --lossy
+    let mut bytes = Vec::new();
+    let mut bytes_read = 0;
+    let mut unterminated_line = None;
+    let mut line_number = 0;
+    let mut results = VecDeque::new();
+    // Numbers of the lines containing the denominator pattern, kept so --tail can drop them too
+    let mut denominator_lines = VecDeque::new();
+    let mut context = VecDeque::new();
+    // Lines that could still be printed before the next match, and how many more follow the last one
+    let mut before = VecDeque::new();
+    let mut after_remaining = 0;
+    // Lines of the paragraph being read with --paragraph, and whether each was selected
+    let mut paragraph = Vec::new();
+    // The last section header read, until a match needs it, and the headers matches needed
+    let mut header = None;
+    let mut sections = VecDeque::new();
+
+    // Counting lines (but not occurrences, distinct lines, bytes or per pattern) never looks at their text
+    let reports_counts = config.count || config.lists_files() || config.denominator.is_some();
+    let reads_text = config.only_matching || config.count_distinct || config.count_bytes || config.count_per_pattern || config.stats;
+    let counts_only = reports_counts && !reads_text;
+
+    let started = Instant::now();
+    let mut timed_out = false;
+
+    loop {
+        // Ctrl-C stops the search between lines, keeping what was found for run to print
+        if signal::interrupted() {
+            break;
+        }
+
+        // A file that has used up its time is left with the matches found so far
+        if over_budget(config.per_file_timeout, line_number, started) {
+            timed_out = true;
+            break;
+        }
+
+        // With --head nothing past the first lines is read at all
+        if config.head_lines.is_some_and(|head| line_number >= head) {
+            break;
+        }
+
+        bytes.clear();
+        // read_until would retry a read broken by Ctrl-C, so lines without a limit are read the same way
+        let (read, cut) = match read_line_capped(&mut reader, &mut bytes, config.max_line_bytes.unwrap_or(usize::MAX)) {
+            Ok(read) => read,
+            // Ctrl-C during a read that was waiting for input stops the search like it does between lines
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => break,
+            Err(e) => return Err(e),
+        };
+        if let Some(limit) = config.max_line_bytes.filter(|_| cut && config.long_lines == LongLines::Error) {
+            let message = format!("line {} is longer than {} bytes", line_number + config.line_base + 1, limit);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        if read == 0 {
+            break;
+        }
+        bytes_read += read as u64;
+        line_number += 1;
+        unterminated_line = if bytes.ends_with(b"\n") { None } else { Some(line_number + config.line_base) };
+        // Invalid UTF-8 is an error unless --lossy turns it into replacement characters
+        let line = if config.lossy_utf8 {
+            String::from_utf8_lossy(&bytes)
+        } else {
+            Cow::Borrowed(std::str::from_utf8(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+        };
+
+        // With --tail only matches in the last lines survive, so older ones are dropped as the window moves
+        if let Some(tail) = config.tail_lines {
+            while results.front().is_some_and(|(number, _)| number + tail <= line_number) {
+                results.pop_front();
+            }
+            while denominator_lines.front().is_some_and(|number| number + tail <= line_number) {
+                denominator_lines.pop_front();
+            }
+            while context.front().is_some_and(|(number, _)| number + tail <= line_number) {
+                context.pop_front();
+            }
+        }
+
+        // Drop the line terminator, as str::lines() does
+        let text = line.strip_suffix('\n').unwrap_or(&line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+
+        // Colour codes are removed before matching so they can't split up a word
+        let cleaned = if config.strip_ansi { strip_ansi(text) } else { Cow::Borrowed(text) };
+        // Transforms change only the text matched against, not the text printed
+        let transformed = transform::apply_all(&config.transforms, Cow::Borrowed(&cleaned));
+        let folded = folds_lines.then(|| fold_case(config, &transformed));
+        let folded_contains = |pattern: &Option<String>| {
+            pattern.as_ref().is_some_and(|pattern| folded.as_deref().is_some_and(|folded| folded.contains(pattern.as_str())))
+        };
+
+        if range_mode {
+            if in_range {
+                // The end line is part of the range, which then closes
+                if folded_contains(&range_end) {
+                    in_range = false;
+                }
+            } else if folded_contains(&range_start) {
+                // Like sed, the end pattern is only looked for from the line after the start
+                in_range = true;
+            } else {
+                continue;
+            }
+        }
+
+        if folded_contains(&denominator) {
+            denominator_lines.push_back(line_number);
+        }
+
+        let printed = if config.print_stripped { &cleaned } else { text };
+
+        // With --columns only part of the line is searched, and short lines have nothing to search
+        let searched = match config.column_range {
+            Some(range) => column_slice(&transformed, range),
+            None => Some(&*transformed),
+        };
+
+        // With -v the lines without the query are the ones selected
+        let selected = searched.is_some_and(|searched| {
+            if config.case_sensitive {
+                patterns.iter().any(|pattern| searched.contains(pattern))
+            } else {
+                compiled.iter().any(|query| query.matches_line(searched))
+            }
+        });
+        let selected = selected
+            && (!in_code_regions || !find_patterns(config, printed).is_empty());
+        if section_pattern.as_ref().zip(folded.as_deref()).is_some_and(|(pattern, folded)| is_section(pattern, folded)) {
+            header = Some((line_number, printed.to_string()));
+        }
+
+        if config.paragraph_mode {
+            // A blank line ends the paragraph, and isn't part of either side
+            if text.trim().is_empty() {
+                flush_paragraph(&mut paragraph, &mut results, &mut context);
+            } else {
+                paragraph.push((line_number, printed.to_string(), selected != config.invert_match));
+            }
+        } else if selected != config.invert_match {
+            context.extend(before.drain(..));
+            after_remaining = config.after_context;
+            // Counts only need to know the line matched, so its text isn't copied
+            let kept = if counts_only { String::new() } else { printed.to_string() };
+            results.push_back((line_number, kept));
+            sections.extend(header.take());
+
+            // The rest of the file is never read for --first-only
+            if config.first_match_per_file {
+                break;
+            }
+        } else if after_remaining > 0 {
+            after_remaining -= 1;
+            context.push_back((line_number, printed.to_string()));
+        } else if config.before_context > 0 {
+            if before.len() == config.before_context {
+                before.pop_front();
+            }
+            before.push_back((line_number, printed.to_string()));
+        }
+    }
+
+    // The last paragraph needn't end with a blank line
+    flush_paragraph(&mut paragraph, &mut results, &mut context);
+
+    // Lines are counted from the start of the stream, so the lines before it are added on
+    for (number, _) in results.iter_mut().chain(context.iter_mut()).chain(sections.iter_mut()) {
+        *number += config.line_base;
+    }
+
+    Ok(Scan {
+        matches: results.into(),
+        denominator: denominator_lines.len(),
+        context: context.into(),
+        bytes_read,
+        unterminated_line,
+        sections: sections.into(),
+        timed_out,
+    })
+}
+
+/// Is section method
+///
+/// # Parameters
+///
+/// `pattern` - The section pattern, case folded like the line
+/// `line` - A line, case folded
+///
+/// # Returns
+///
+/// `bool` - True when the line is a section header
+fn is_section(pattern: &str, line: &str) -> bool {
+    match pattern.strip_prefix('^') {
+        Some(start) => line.starts_with(start),
+        None => line.contains(pattern),
+    }
+}
+
+/// Flush paragraph method
+///
+/// Keeps the lines of a finished paragraph if any of them was selected, the selected ones as
+/// matches and the rest as context around them
+///
+/// # Parameters
+///
+/// `paragraph` - Number, text and whether it was selected of each line, emptied
+/// `results` - Matching lines kept so far
+/// `context` - Context lines kept so far
+fn flush_paragraph(
+    paragraph: &mut Vec<(usize, String, bool)>,
+    results: &mut VecDeque<(usize, String)>,
+    context: &mut VecDeque<(usize, String)>,
+) {
+    if paragraph.iter().any(|(_, _, selected)| *selected) {
+        for (number, text, selected) in paragraph.drain(..) {
+            if selected {
+                results.push_back((number, text));
+            } else {
+                context.push_back((number, text));
+            }
+        }
+    }
+    paragraph.clear();
+}
+
+/// Read line capped method
+///
+/// Reads a line like `read_until`, but keeps no more than `limit` bytes of it. The rest is
+/// read past a buffer at a time without being stored, so the next read starts on the next
+/// line. A cut line keeps its newline, so it still counts as terminated, and is cut back to
+/// the last whole UTF-8 character. A read broken by a signal is tried again, unless it was
+/// Ctrl-C, whose `ErrorKind::Interrupted` error is returned so the search stops.
+///
+/// # Parameters
+///
+/// `reader` - Source of the lines
+/// `line` - Buffer the kept bytes are added to
+/// `limit` - Most bytes of the line to keep, its newline not included
+///
+/// # Returns
+///
+/// `io::Result<(usize, bool)>` - Bytes read from the reader, the newline included, and whether
+/// the line was cut
+fn read_line_capped<R: BufRead>(reader: &mut R, line: &mut Vec<u8>, limit: usize) -> io::Result<(usize, bool)> {
+    let mut read = 0;
+    let mut cut = false;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted && !signal::interrupted() => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            break;
+        }
+
+        let newline = available.iter().position(|&byte| byte == b'\n');
+        let used = newline.map_or(available.len(), |index| index + 1);
+        let content = &available[..newline.unwrap_or(used)];
+        let room = limit.saturating_sub(line.len());
+        cut |= content.len() > room;
+        line.extend_from_slice(&content[..content.len().min(room)]);
+        if newline.is_some() {
+            line.push(b'\n');
+        }
+
+        reader.consume(used);
+        read += used;
+        if newline.is_some() {
+            break;
+        }
+    }
+
+    // A character split by the limit is dropped, rather than left as invalid UTF-8
+    if cut {
+        let end = if line.ends_with(b"\n") { line.len() - 1 } else { line.len() };
+        if let Err(e) = std::str::from_utf8(&line[..end]) {
+            if e.error_len().is_none() {
+                let newline = line.ends_with(b"\n");
+                line.truncate(e.valid_up_to());
+                if newline {
+                    line.push(b'\n');
+                }
+            }
+        }
+    }
+    Ok((read, cut))
+}
+
+/// Search config reader method
+///
+/// Searches text from any reader with the full set of options, for callers that hold the
+/// text in memory rather than in a file
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution, whose filenames are ignored
+/// `reader` - Source of the lines to search
+///
+/// # Returns
+///
+/// `io::Result<Vec<(usize, String)>>` - Line number (starting at 1) and text of each selected line
+pub fn search_config_reader<R: BufRead>(config: &Config, reader: R) -> io::Result<Vec<(usize, String)>> {
+    search_stream(config, reader).map(|scan| scan.matches)
+}
+
+/// Search reader method
+///
+/// Searches lines read one at a time from any reader, such as a socket or a decompressor,
+/// without the whole text ever being held in memory
+///
+/// # Arguments
+///
+/// * `query` - The search query -- see Config for more information
+/// * `reader` - Source of the lines to search
+/// * `case_sensitive` - Whether letter case must match
+///
+/// # Returns
+///
+/// `Result<Vec<(usize, String)>, io::Error>` - Line number (starting at 1) and text of each
+/// line containing the query, or the first error from the reader (including invalid UTF-8)
+pub fn search_reader<R: BufRead>(query: &str, reader: R, case_sensitive: bool) -> Result<Vec<(usize, String)>, io::Error> {
+    let compiled = CompiledQuery::new(query);
+    let mut results = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let found = if case_sensitive { line.contains(query) } else { compiled.matches_line(&line) };
+        if found {
+            results.push((index + 1, line));
+        }
+    }
+
+    Ok(results)
+}
+
+/// Strip ANSI method
+///
+/// Removes SGR escape sequences (`ESC [ ... m`, used for colours and text styles) from a
+/// line. Other escape sequences are left alone.
+///
+/// # Parameters
+///
+/// `text` - Text that may contain escape sequences
+///
+/// # Returns
+///
+/// `Cow<str>` - The text without SGR sequences, borrowed when there were none
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(ansi_free_parts(text).into_iter().map(|part| &text[part]).collect())
+}
+
+/// ANSI free parts method
+///
+/// # Parameters
+///
+/// `text` - Text that may contain escape sequences
+///
+/// # Returns
+///
+/// `Vec<Range<usize>>` - Byte ranges of the text between its SGR sequences, in order
+fn ansi_free_parts(text: &str) -> Vec<Range<usize>> {
+    let mut parts = Vec::new();
+    // Start of the part being read, and where to look for the next escape from
+    let mut start = 0;
+    let mut from = 0;
+
+    while let Some(found) = text[from..].find('\x1b') {
+        let escape = from + found;
+        let sequence = &text[escape + 1..];
+
+        // Parameters are digits separated by semicolons, ending with 'm'
+        let parameters = sequence
+            .strip_prefix('[')
+            .map(|after| after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit() || c == ';').len());
+
+        match parameters {
+            Some(length) if sequence[1 + length..].starts_with('m') => {
+                parts.push(start..escape);
+                start = escape + length + 3;
+                from = start;
+            }
+            // Not an SGR sequence, keep the escape character
+            _ => from = escape + 1,
+        }
+    }
+
+    parts.push(start..text.len());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Column slice method
+///
+/// # Parameters
+///
+/// `text` - A line
+/// `range` - First and last byte columns, counted from 1
+///
+/// # Returns
+///
+/// `Option<&str>` - The text in those columns, or None if the line ends before the first.
+/// Columns splitting a character are narrowed to the whole characters inside them.
+fn column_slice(text: &str, (start, end): (usize, usize)) -> Option<&str> {
+    let mut start = start - 1;
+    if start >= text.len() {
+        return None;
+    }
+    let mut end = end.min(text.len());
+
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(text.get(start..end).unwrap_or_default())
+}
+
+/// Fold case method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `text` - Text about to be compared
+///
+/// # Returns
+///
+/// `Cow<str>` - The text unchanged for case sensitive searches, otherwise lowercased
+fn fold_case<'a>(config: &Config, text: &'a str) -> Cow<'a, str> {
+    if config.case_sensitive {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(text.to_lowercase())
+    }
+}
+
+/// Search method
+///
+/// Performs case sensitive search
+///
+/// # Arguments
+///
+/// * `query` - The search query -- see Config for more information
+/// * `contents` - Contents of the file
+///
+/// # Returns
+///
+/// `Vec<&'a str>` - Vector of lines that contain the search query
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    // Builds results vector
+    let mut results = Vec::new();
+
+    // Iterates through contents lines
+    for line in contents.lines() {
+        // Check if the line contains the query
+        if line.contains(query) {
+            // If it does push it to results vector
+            results.push(line);
+        }
+    }
+
+    // Returns results
+    results
+}
+
+/// Search method -- case insenitive
+///
+/// Performs a case insensitive search
+///
+/// # Arguments
+///
+/// * `query` - The search query -- see Config for more information
+/// * `contents` - Contents of the file
+///
+/// # Returns
+///
+/// `Vec<&'a str>` - Vector of lines that contain the search query
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    // Lowercases the query once for every line
+    let query = CompiledQuery::new(query);
+
+    // See search() for other comments
+    let mut results = Vec::new();
+
+    for line in contents.lines() {
+        if query.matches_line(line) {
+            results.push(line);
+        }
+    }
+
+    results
+}
+
+/// Search count method
+///
+/// Counts the lines containing the query without collecting them
+///
+/// # Arguments
+///
+/// * `query` - The search query -- see Config for more information
+/// * `contents` - Contents of the file
+/// * `case_sensitive` - Whether letter case must match
+///
+/// # Returns
+///
+/// `usize` - Number of lines that contain the search query
+pub fn search_count(query: &str, contents: &str, case_sensitive: bool) -> usize {
+    if case_sensitive {
+        contents.lines().filter(|line| line.contains(query)).count()
+    } else {
+        let query = CompiledQuery::new(query);
+        contents.lines().filter(|line| query.matches_line(line)).count()
+    }
+}
+
+/// Search occurrences method
+///
+/// Counts every non-overlapping occurrence of the query, so a line containing it three times counts 3
+///
+/// # Arguments
+///
+/// * `query` - The search query -- an empty query occurs nowhere
+/// * `contents` - Contents of the file
+/// * `case_sensitive` - Whether letter case must match
+///
+/// # Returns
+///
+/// `usize` - Number of occurrences of the search query
+pub fn search_occurrences(query: &str, contents: &str, case_sensitive: bool) -> usize {
+    contents.lines().map(|line| find_all(query, line, case_sensitive).len()).sum()
+}
+
+/// Compiled query struct
+///
+/// A query prepared for case insensitive matching against many lines. The query is
+/// lowercased once, and each line is lowercased into a buffer that is kept between calls,
+/// so matching a line only allocates when it is longer than any line before it.
+///
+/// # Arguments
+///
+/// * `lowercase` - The query in lowercase
+/// * `scratch` - Buffer the line being matched is lowercased into
+pub struct CompiledQuery {
+    lowercase: String,
+    scratch: RefCell<String>,
+}
+
+impl CompiledQuery {
+    /// Compiled query constructor
+    ///
+    /// # Parameters
+    ///
+    /// * `query` - The search query
+    ///
+    /// # Returns
+    ///
+    /// * `CompiledQuery` - The prepared query
+    pub fn new(query: &str) -> CompiledQuery {
+        CompiledQuery { lowercase: query.to_lowercase(), scratch: RefCell::new(String::new()) }
+    }
+
+    /// Matches line method
+    ///
+    /// # Parameters
+    ///
+    /// `line` - The line to search
+    ///
+    /// # Returns
+    ///
+    /// `bool` - True when the line contains the query, ignoring case
+    pub fn matches_line(&self, line: &str) -> bool {
+        let mut scratch = self.scratch.borrow_mut();
+        scratch.clear();
+        if line.is_ascii() {
+            scratch.push_str(line);
+            scratch.make_ascii_lowercase();
+        } else {
+            // Lowercased a character at a time, which differs from str::to_lowercase only for a final sigma
+            for c in line.chars() {
+                scratch.extend(c.to_lowercase());
+            }
+        }
+
+        scratch.contains(self.lowercase.as_str())
+    }
+}
+
+/// Searched text struct
+///
+/// The text of a line that the patterns are looked for in, as the line was when it was
+/// selected, with where each of its bytes came from in the line
+///
+/// # Arguments
+///
+/// * `text` - The line without its colour codes with `--strip-ansi`, after its transforms,
+///   cut down to `--columns`
+/// * `origins` - For each byte of `text`, the bytes of the line it was made from
+struct SearchedText {
+    text: String,
+    origins: Vec<Range<usize>>,
+}
+
+impl SearchedText {
+    /// Searched text constructor
+    ///
+    /// # Parameters
+    ///
+    /// * `config` - The given config of the execution
+    /// * `line` - The printed line
+    /// * `columns` - The columns searched, if limited
+    ///
+    /// # Returns
+    ///
+    /// * `SearchedText` - The text the line was selected on
+    fn new(config: &Config, line: &str, columns: Option<(usize, usize)>) -> SearchedText {
+        // Colour codes are left out, but their bytes are still counted in the origins
+        let parts = if config.strip_ansi { ansi_free_parts(line) } else { std::iter::once(0..line.len()).collect() };
+        let mut searched = SearchedText {
+            text: parts.iter().map(|part| &line[part.clone()]).collect(),
+            origins: parts.into_iter().flatten().map(|index| index..index + 1).collect(),
+        };
+        for transform in &config.transforms {
+            searched = match transform {
+                // Lower casing can change the length of a character, so each one is mapped to its original
+                Transform::Lowercase => {
+                    let mut lowered = SearchedText { text: String::with_capacity(searched.text.len()), origins: Vec::new() };
+                    for (index, c) in searched.text.char_indices() {
+                        let origin = searched.origins[index].start..searched.origins[index + c.len_utf8() - 1].end;
+                        for lower in c.to_lowercase() {
+                            lowered.text.push(lower);
+                            lowered.origins.extend(std::iter::repeat_n(origin.clone(), lower.len_utf8()));
+                        }
+                    }
+                    lowered
+                }
+                // The rest keep part of the text as it was
+                _ => {
+                    let kept = transform.apply(&searched.text);
+                    let start = kept.as_ptr() as usize - searched.text.as_ptr() as usize;
+                    let kept = start..start + kept.len();
+                    searched.part(kept)
+                }
+            };
+        }
+
+        // Columns are counted in the transformed text, as they are when selecting lines
+        if let Some(columns) = columns {
+            let kept = column_slice(&searched.text, columns).map_or(0..0, |kept| {
+                let start = kept.as_ptr() as usize - searched.text.as_ptr() as usize;
+                start..start + kept.len()
+            });
+            searched = searched.part(kept);
+        }
+        searched
+    }
+
+    /// Part method
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - Byte range of the text to keep
+    ///
+    /// # Returns
+    ///
+    /// * `SearchedText` - Only that part of the text
+    fn part(self, range: Range<usize>) -> SearchedText {
+        SearchedText { text: self.text[range.clone()].to_string(), origins: self.origins[range].to_vec() }
+    }
+
+    /// Origin method
+    ///
+    /// # Parameters
+    ///
+    /// * `range` - Byte range of a match in the searched text, which can't be empty
+    ///
+    /// # Returns
+    ///
+    /// * `Range<usize>` - Byte range of the line it was found in
+    fn origin(&self, range: &Range<usize>) -> Range<usize> {
+        self.origins[range.start].start..self.origins[range.end - 1].end
+    }
+}
+
+/// Find patterns method
+///
+/// Finds the non-overlapping occurrences of any of the config's patterns, in the same text
+/// the line was selected on. Where two overlap the one starting first is kept, the longer if
+/// they start together.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `line` - The line to search
+///
+/// # Returns
+///
+/// `Vec<Range<usize>>` - Byte range of each occurrence in the line, in order
+fn find_patterns(config: &Config, line: &str) -> Vec<Range<usize>> {
+    find_matches(config, line, true)
+}
+
+/// Find printed method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `text` - Text about to be printed, the whole line or with -o just a match in it
+///
+/// # Returns
+///
+/// `Vec<Range<usize>>` - Byte range of each occurrence, like `find_patterns`. A match printed
+/// on its own is already inside the columns and code regions, which can't be told from it.
+fn find_printed(config: &Config, text: &str) -> Vec<Range<usize>> {
+    find_matches(config, text, !config.only_matching)
+}
+
+/// Find matches method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `line` - The line to search
+/// `whole_line` - Whether this is a whole line, so its matches must be in `--columns` and the
+/// comments or strings of `--in-comments` and `--in-strings`
+///
+/// # Returns
+///
+/// `Vec<Range<usize>>` - Byte range of each occurrence in the line, in order and not overlapping
+fn find_matches(config: &Config, line: &str, whole_line: bool) -> Vec<Range<usize>> {
+    let columns = config.column_range.filter(|_| whole_line);
+    let find = |text: &str| -> Vec<Range<usize>> {
+        config.patterns().iter().flat_map(|pattern| find_all(pattern, text, config.case_sensitive)).collect()
+    };
+    let mut found = if !config.strip_ansi && config.transforms.is_empty() && columns.is_none() {
+        find(line)
+    } else {
+        let searched = SearchedText::new(config, line, columns);
+        find(&searched.text).iter().map(|range| searched.origin(range)).collect()
+    };
+    found.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+
+    let mut kept: Vec<Range<usize>> = Vec::new();
+    for range in found {
+        if kept.last().is_none_or(|last| range.start >= last.end) {
+            kept.push(range);
+        }
+    }
+
+    // The parts of source lines matches must be in, for --in-comments and --in-strings
+    let regions: Vec<code::Region> = [(config.in_comments, code::Region::Comment), (config.in_strings, code::Region::String)]
+        .iter()
+        .filter_map(|&(wanted, region)| (wanted && whole_line).then_some(region))
+        .collect();
+    if regions.is_empty() {
+        kept
+    } else {
+        code::within(line, kept, &regions)
+    }
+}
+
+/// Find all method
+///
+/// Finds every non-overlapping occurrence of the query in a line, scanning left to right.
+/// Ranges are byte offsets into the original line even for case insensitive searches, so
+/// they can be used to slice out or highlight the matched text.
+///
+/// # Arguments
+///
+/// * `query` - The search query -- an empty query matches nowhere
+/// * `line` - The line to search
+/// * `case_sensitive` - Whether letter case must match
+///
+/// # Returns
+///
+/// `Vec<Range<usize>>` - Byte range of each occurrence
+pub fn find_all(query: &str, line: &str, case_sensitive: bool) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    if case_sensitive {
+        return line.match_indices(query).map(|(start, found)| start..start + found.len()).collect();
+    }
+
+    // Lowercased query compared character by character against the lowercased line
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while let Some(c) = line[start..].chars().next() {
+        match folded_prefix_len(&line[start..], &query) {
+            // Continue after the occurrence so matches never overlap
+            Some(len) => {
+                ranges.push(start..start + len);
+                start += len;
+            }
+            None => start += c.len_utf8(),
+        }
+    }
+
+    ranges
+}
+
+/// Folded prefix length method
+///
+/// # Arguments
+///
+/// * `text` - Text that may start with the query
+/// * `query` - Lowercased query characters
+///
+/// # Returns
+///
+/// `Option<usize>` - Byte length of the prefix of `text` that matches the query ignoring case
+fn folded_prefix_len(text: &str, query: &[char]) -> Option<usize> {
+    let mut remaining = query;
+
+    for (index, c) in text.char_indices() {
+        if remaining.is_empty() {
+            return Some(index);
+        }
+
+        for lower in c.to_lowercase() {
+            match remaining.split_first() {
+                Some((&expected, rest)) if expected == lower => remaining = rest,
+                _ => return None,
+            }
+        }
+    }
+
+    if remaining.is_empty() { Some(text.len()) } else { None }
+}
+ 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an argument list as main() would receive it
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Writes a file to a per-process temporary directory and returns its path
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("grep_remake_tests_{}", std::process::id())).join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// One result test
+    ///
+    /// Test if the correct result is returned when one is desired
+    #[test]
+    fn one_result() {
+        let query = "Testing";
+        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
+        
+        assert_eq!(
+            vec!["It contains a line that says Testing which should be found by the program"], 
+            search(query, contents)
+        );
+    }
+
+    /// Multiple result test
+    ///
+    /// Test if the correct result is returned when several are desired
+    #[test]
+    fn multiple_result() {
+        let query = "contains";
+        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
+        
+        assert_eq!(
+            vec!["It contains a line that says Testing which should be found by the program", "It also contains another LINE that does not contain the above term that should not be found"], 
+            search(query, contents)
+        );
+    }
+
+    /// Case sensitive result test
+    ///
+    /// Test if the correct result is returned case sensitive
+    #[test]
+    fn case_sensitive() {
+        let query = "line";
+        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
+
+        assert_eq!(
+            vec!["It contains a line that says Testing which should be found by the program"], 
+            search(query, contents)
+        );
+    }
+
+    /// Case sensitive result test
+    ///
+    /// Test if the correct result is returned case insensitive
+    #[test]
+    fn case_insensitive() {
+        let query = "lInE";
+        let contents = "This is a string\nIt contains a line that says Testing which should be found by the program\nIt also contains another LINE that does not contain the above term that should not be found";
+
+        assert_eq!(
             vec!["It contains a line that says Testing which should be found by the program", "It also contains another LINE that does not contain the above term that should not be found"], 
             search_case_insensitive(query, contents)
         );
     }
-}
\ No newline at end of file
+
+    /// Option order test
+    ///
+    /// Test if options before, between and after the positionals give the same config, and "--" still ends them
+    #[test]
+    fn option_order() {
+        let leading = Config::new(&args(&["grep_remake", "-i", "-n", "query", "a.txt", "b.txt"])).unwrap();
+        let middle = Config::new(&args(&["grep_remake", "-i", "query", "a.txt", "-n", "b.txt"])).unwrap();
+        let trailing = Config::new(&args(&["grep_remake", "query", "a.txt", "b.txt", "-i", "-n"])).unwrap();
+
+        for config in [&leading, &middle, &trailing] {
+            assert_eq!("query", config.query);
+            assert_eq!(vec!["a.txt", "b.txt"], config.filenames);
+            assert!(!config.case_sensitive);
+            assert!(config.line_number);
+        }
+
+        let config = Config::new(&args(&["grep_remake", "-n", "query", "--", "-i", "a.txt"])).unwrap();
+        assert_eq!(vec!["-i", "a.txt"], config.filenames);
+        assert!(config.case_sensitive);
+    }
+
+    /// Parallel options test
+    ///
+    /// Test if the thread count and streaming flag are parsed before the positionals
+    #[test]
+    fn parallel_options() {
+        let config = Config::new(&args(&["grep_remake", "-j", "4", "--no-sort", "query", "a.txt", "b.txt"])).unwrap();
+
+        assert_eq!(4, config.threads);
+        assert!(config.streaming);
+        assert_eq!("query", config.query);
+        assert_eq!(vec!["a.txt", "b.txt"], config.filenames);
+        assert_eq!(0, Config::new(&args(&["grep_remake", "query", "a.txt"])).unwrap().threads);
+        assert_eq!(0, Config::new(&args(&["grep_remake", "-j", "0", "query", "a.txt"])).unwrap().threads);
+    }
+
+    /// Parallel order test
+    ///
+    /// Test if parallel results come out in argument order by default and are all present when streaming
+    #[test]
+    fn parallel_order() {
+        let mut list = vec!["grep_remake".to_string(), "-j".to_string(), "3".to_string(), "match".to_string()];
+        for index in 0..8 {
+            list.push(temp_file(&format!("parallel_{}.txt", index), &format!("match {}\nnothing\n", index)));
+        }
+        let mut config = Config::new(&list).unwrap();
+        let files: Vec<Target> = config.filenames.iter().map(|filename| Target::new(filename)).collect();
+
+        let mut sorted = Vec::new();
+        search_parallel(&config, &files, true, &mut |output| {
+            sorted.extend(output.lines);
+            Ok(())
+        }).unwrap();
+        let expected: Vec<String> = config.filenames.iter().enumerate()
+            .map(|(index, filename)| format!("{}:match {}", filename, index))
+            .collect();
+        assert_eq!(expected, sorted);
+
+        config.streaming = true;
+        let mut streamed = Vec::new();
+        search_parallel(&config, &files, true, &mut |output| {
+            streamed.extend(output.lines);
+            Ok(())
+        }).unwrap();
+        streamed.sort();
+        let mut expected = expected;
+        expected.sort();
+        assert_eq!(expected, streamed);
+    }
+
+    /// Range mode test
+    ///
+    /// Test if only lines inside start/end ranges are searched, and that a later start reopens the range
+    #[test]
+    fn range_mode() {
+        let config = Config::new(&args(&["grep_remake", "--range-start", "BEGIN", "--range-end", "END", "x", "file"])).unwrap();
+        let contents = "x outside\nBEGIN x\nx inside\nEND x\nx outside again\nBEGIN\nx reopened\nEND\n";
+
+        assert_eq!(
+            vec![(2, "BEGIN x".to_string()), (3, "x inside".to_string()), (4, "END x".to_string()), (7, "x reopened".to_string())],
+            search_stream(&config, contents.as_bytes()).unwrap().matches
+        );
+    }
+
+    /// Unclosed range test
+    ///
+    /// Test if a range without a closing line runs to the end of the file
+    #[test]
+    fn range_never_closes() {
+        let config = Config::new(&args(&["grep_remake", "--range-start", "BEGIN", "--range-end", "END", "x", "file"])).unwrap();
+        let contents = "x before\nBEGIN\nx one\nx two";
+
+        assert_eq!(
+            vec![(3, "x one".to_string()), (4, "x two".to_string())],
+            search_stream(&config, contents.as_bytes()).unwrap().matches
+        );
+    }
+
+    /// File list test
+    ///
+    /// Test if the paths in a --files-from list are used, skipping blank lines and missing files
+    #[test]
+    fn files_from_list() {
+        let first = temp_file("list_first.txt", "query\n");
+        let second = temp_file("list_second.txt", "query\n");
+        let list = temp_file("list.txt", &format!("{}\n\n{}\n  \n/no/such/file.txt\n", first, second));
+
+        let config = Config::new(&args(&["grep_remake", "--files-from", &list, "query"])).unwrap();
+
+        let mut err = Vec::new();
+        assert_eq!((vec![Target::new(&first), Target::new(&second)], 1), collect_files(&config, &mut err).unwrap());
+        assert!(String::from_utf8(err).unwrap().starts_with("grep_remake: /no/such/file.txt: "));
+    }
+
+    /// Tab expansion test
+    ///
+    /// Test if tabs are expanded to the next tab stop rather than a fixed number of spaces
+    #[test]
+    fn tab_expansion() {
+        assert_eq!("    a   bc      d", expand_tabs("\ta\tbc\t\td", 4));
+        assert_eq!("abcd    e", expand_tabs("abcd\te", 4));
+        assert_eq!("no tabs", expand_tabs("no tabs", 8));
+    }
+
+    /// Tab display test
+    ///
+    /// Test if the match runs on the original line while only the displayed text is expanded
+    #[test]
+    fn tabs_display_only() {
+        let filename = temp_file("tabs.txt", "key\tvalue\nother\n");
+        let config = Config::new(&args(&["grep_remake", "--tabs", "8", "y\tv", &filename])).unwrap();
+
+        assert_eq!(vec!["key     value"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Find all test
+    ///
+    /// Test if occurrences are found left to right without overlapping
+    #[test]
+    fn find_all_non_overlapping() {
+        assert_eq!(vec![0..2, 2..4], find_all("aa", "aaaa", true));
+        assert_eq!(vec![0..2, 2..4], find_all("aa", "aaaaa", true));
+        assert_eq!(vec![4..7, 13..16], find_all("the", "And the cat, the end", true));
+    }
+
+    /// Find all empty query test
+    ///
+    /// Test if an empty query is defined as matching nowhere
+    #[test]
+    fn find_all_empty_query() {
+        assert!(find_all("", "anything", true).is_empty());
+        assert!(find_all("", "anything", false).is_empty());
+    }
+
+    /// Find all case insensitive test
+    ///
+    /// Test if case insensitive ranges are byte offsets into the original line
+    #[test]
+    fn find_all_case_insensitive() {
+        assert_eq!(vec![0..3, 6..9], find_all("lIn", "LINE  line", false));
+        // "Ä" is two bytes, so ranges are three bytes long
+        assert_eq!(vec![0..3, 5..8, 9..12], find_all("äb", "ÄBc Äb ÄB", false));
+    }
+
+    /// Head lines test
+    ///
+    /// Test if matches after the first N lines are ignored with --head
+    #[test]
+    fn head_lines() {
+        let contents: String = (1..=30)
+            .map(|number| if number == 5 || number == 20 { "match\n".to_string() } else { format!("line {}\n", number) })
+            .collect();
+        let config = Config::new(&args(&["grep_remake", "--head", "10", "match", "file"])).unwrap();
+
+        assert_eq!(vec![(5, "match".to_string())], search_stream(&config, contents.as_bytes()).unwrap().matches);
+    }
+
+    /// Tail lines test
+    ///
+    /// Test if only matches in the last N lines are kept, with their real line numbers
+    #[test]
+    fn tail_lines() {
+        let contents: String = (1..=50)
+            .map(|number| if number == 3 || number == 47 { format!("match {}\n", number) } else { format!("line {}\n", number) })
+            .collect();
+        let config = Config::new(&args(&["grep_remake", "--tail", "5", "match", "file"])).unwrap();
+
+        assert_eq!(vec![(47, "match 47".to_string())], search_stream(&config, contents.as_bytes()).unwrap().matches);
+    }
+
+    /// Line number test
+    ///
+    /// Test if -n prefixes lines with their number, after the filename
+    #[test]
+    fn line_number_prefix() {
+        let filename = temp_file("numbered.txt", "one\ntwo match\nthree\nfour match\n");
+        let config = Config::new(&args(&["grep_remake", "-n", "--tail", "2", "match", &filename])).unwrap();
+
+        assert_eq!(vec![format!("{}:4:four match", filename)], search_file(&config, &Target::new(&filename), true).unwrap().lines);
+    }
+
+    /// Captured output test
+    ///
+    /// Test if run writes its results to the given writer
+    #[test]
+    fn run_captures_output() {
+        let first = temp_file("capture_first.txt", "a match\nno\n");
+        let second = temp_file("capture_second.txt", "another match\n");
+        let config = Config::new(&args(&["grep_remake", "-n", "match", &first, &second])).unwrap();
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        assert_eq!(0, run(config, &mut out, &mut err).unwrap());
+
+        assert_eq!(format!("{}:1:a match\n{}:1:another match\n", first, second), String::from_utf8(out).unwrap());
+        assert!(err.is_empty());
+    }
+
+    /// Flushed on error test
+    ///
+    /// Test if output from before an unreadable file is still written, the files after it are
+    /// still searched and the status is 2
+    #[test]
+    fn run_flushes_on_error() {
+        let first = temp_file("flush_first.txt", "a match\n");
+        let last = temp_file("flush_last.txt", "another match\n");
+        let config = Config::new(&args(&["grep_remake", "match", &first, "/no/such/file.txt", &last])).unwrap();
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        assert_eq!(2, run(config, &mut out, &mut err).unwrap());
+        assert_eq!(format!("{}:a match\n{}:another match\n", first, last), String::from_utf8(out).unwrap());
+        assert!(String::from_utf8(err).unwrap().starts_with("grep_remake: /no/such/file.txt: "));
+    }
+
+    /// Corrupt archive test
+    ///
+    /// Test if a corrupt archive is reported as a warning naming it, and the files after it are still searched
+    #[test]
+    fn corrupt_archive_skipped() {
+        let broken = temp_file("corrupt_archive.tar", &"z".repeat(1024));
+        let after = temp_file("corrupt_archive_after.txt", "a match\n");
+        let config = Config::new(&args(&["grep_remake", "--archives", "match", &broken, &after])).unwrap();
+
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        assert_eq!(2, run(config, &mut out, &mut err).unwrap());
+        assert_eq!(format!("{}:a match\n", after), String::from_utf8(out).unwrap());
+        let err = String::from_utf8(err).unwrap();
+        assert!(err.starts_with(&format!("grep_remake: {}: corrupt tar archive (", broken)), "{}", err);
+    }
+
+    /// Output buffer size test
+    ///
+    /// Test if the output is the same however small the buffer it is collected in
+    #[test]
+    fn output_buffer_sizes() {
+        let contents: String = (0..200).map(|number| format!("line {} match\n", number)).collect();
+        let filename = temp_file("buffer_size.txt", &contents);
+        let output = |options: &[&str]| {
+            let mut list = vec!["grep_remake", "-n"];
+            list.extend(options);
+            list.extend(["match", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&list)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let buffered = output(&[]);
+        assert_eq!(200, buffered.lines().count());
+        assert_eq!(buffered, output(&["--output-buffer-size", "1"]));
+        assert_eq!(buffered, output(&["--output-buffer-size=0"]));
+    }
+
+    /// Strip ANSI test
+    ///
+    /// Test if colour codes are removed while other escapes and text are kept
+    #[test]
+    fn strip_ansi_codes() {
+        assert_eq!("error: failed", strip_ansi("\x1b[31merror\x1b[0m: \x1b[1;4mfailed\x1b[m"));
+        assert_eq!("\x1b[2Kline", strip_ansi("\x1b[2Kline"));
+        assert_eq!("trailing \x1b", strip_ansi("trailing \x1b"));
+    }
+
+    /// Coloured input test
+    ///
+    /// Test if colour codes inside a word don't stop it matching, and which version of the line is printed
+    #[test]
+    fn strip_ansi_matching() {
+        let contents = "\x1b[31mer\x1b[1mror\x1b[0m here\nfine\n";
+
+        let config = Config::new(&args(&["grep_remake", "error", "file"])).unwrap();
+        assert!(search_stream(&config, contents.as_bytes()).unwrap().matches.is_empty());
+
+        let config = Config::new(&args(&["grep_remake", "--strip-ansi", "error", "file"])).unwrap();
+        assert_eq!(
+            vec![(1, "\x1b[31mer\x1b[1mror\x1b[0m here".to_string())],
+            search_stream(&config, contents.as_bytes()).unwrap().matches
+        );
+
+        let config = Config::new(&args(&["grep_remake", "--print-stripped", "error", "file"])).unwrap();
+        assert_eq!(vec![(1, "error here".to_string())], search_stream(&config, contents.as_bytes()).unwrap().matches);
+
+        // Matches run over the colour codes inside them, and are printed with them
+        let filename = temp_file("strip_ansi_matches.txt", contents);
+        let config = Config::new(&args(&["grep_remake", "--strip-ansi", "-o", "error", &filename])).unwrap();
+        assert_eq!(vec!["er\x1b[1mror"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+        let config = Config::new(&args(&["grep_remake", "--strip-ansi", "-oc", "r", &filename])).unwrap();
+        assert_eq!(vec!["4"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Escape sequence test
+    ///
+    /// Test if --escape turns \t into a tab that matches one, while without it the backslash is literal
+    #[test]
+    fn escaped_tab() {
+        let contents = "name\tvalue\nname value\nname\\tvalue\n";
+
+        let config = Config::new(&args(&["grep_remake", "--escape", "name\\tvalue", "file"])).unwrap();
+        assert_eq!("name\tvalue", config.query);
+        assert_eq!(vec![(1, "name\tvalue".to_string())], search_stream(&config, contents.as_bytes()).unwrap().matches);
+
+        let config = Config::new(&args(&["grep_remake", "name\\tvalue", "file"])).unwrap();
+        assert_eq!(vec![(3, "name\\tvalue".to_string())], search_stream(&config, contents.as_bytes()).unwrap().matches);
+
+        assert_eq!(Ok("a\0b\\c\x01,".to_string()), unescape_query("a\\0b\\\\c\\x01\\x2c"));
+        assert!(unescape_query("\\x8f").is_err());
+        assert!(unescape_query("\\q").is_err());
+    }
+
+    /// Comment and string test
+    ///
+    /// Test if --in-comments and --in-strings keep only the lines with a match inside one
+    #[test]
+    fn comments_and_strings() {
+        let contents = "todo();
+// todo: remove
+log(\"todo\");
+int todo = 1; /* todo */
+";
+        let selected = |options: &[&str]| -> Vec<usize> {
+            let mut arguments = vec!["grep_remake"];
+            arguments.extend(options);
+            arguments.extend(["todo", "file"]);
+            let config = Config::new(&args(&arguments)).unwrap();
+            search_stream(&config, contents.as_bytes()).unwrap().matches.into_iter().map(|(number, _)| number).collect()
+        };
+
+        assert_eq!(vec![1, 2, 3, 4], selected(&[]));
+        assert_eq!(vec![2, 4], selected(&["--in-comments"]));
+        assert_eq!(vec![3], selected(&["--in-strings"]));
+        assert_eq!(vec![2, 3, 4], selected(&["--in-comments", "--in-strings"]));
+    }
+
+    /// Transform test
+    ///
+    /// Test if trimmed lines are matched from their first non-blank column but printed padded as they were
+    #[test]
+    fn transform_trim() {
+        let contents = "    error: indented\nerror: flush\n  warning: error later\n";
+
+        let config = Config::new(&args(&["grep_remake", "--columns", "1:5", "error", "file"])).unwrap();
+        assert_eq!(vec![(2, "error: flush".to_string())], search_stream(&config, contents.as_bytes()).unwrap().matches);
+
+        let config = Config::new(&args(&["grep_remake", "--transform", "trim", "--columns", "1:5", "error", "file"])).unwrap();
+        assert_eq!(
+            vec![(1, "    error: indented".to_string()), (2, "error: flush".to_string())],
+            search_stream(&config, contents.as_bytes()).unwrap().matches
+        );
+
+        let config = Config::new(&args(&["grep_remake", "--transform", "trim,lowercase", "q", "f"])).unwrap();
+        assert_eq!(vec![Transform::Trim, Transform::Lowercase], config.transforms);
+        assert!(Config::new(&args(&["grep_remake", "--transform", "upper", "q", "f"])).is_err());
+    }
+
+    /// Transformed match test
+    ///
+    /// Test if -o and -oc find matches in the transformed text the line was selected on, printing them as they were
+    #[test]
+    fn transformed_matches() {
+        let filename = temp_file("transformed_matches.txt", "FOO bar foo\n  path/to/Foo.rs\n");
+
+        let config = Config::new(&args(&["grep_remake", "--transform", "lowercase", "-o", "foo", &filename])).unwrap();
+        assert_eq!(vec!["FOO", "foo", "Foo"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "--transform", "lowercase", "-oc", "foo", &filename])).unwrap();
+        assert_eq!(vec!["3"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        // Only what is left of the line after the basename is searched
+        let config = Config::new(&args(&["grep_remake", "--transform", "trim,basename,lowercase", "-o", "foo", &filename])).unwrap();
+        assert_eq!(vec![10..13], find_patterns(&config, "  path/to/Foo.rs"));
+        assert!(find_patterns(&config, "foo/bar.rs").is_empty());
+    }
+
+    /// Column match test
+    ///
+    /// Test if only the matches inside --columns are printed, counted, coloured and replaced
+    #[test]
+    fn column_matches() {
+        let filename = temp_file("column_matches.txt", "0123foo789foo\n");
+        let search = |extra: &[&str]| {
+            let mut arguments = vec!["grep_remake", "--columns", "1:8"];
+            arguments.extend_from_slice(extra);
+            arguments.extend_from_slice(&["foo", &filename]);
+            search_file(&Config::new(&args(&arguments)).unwrap(), &Target::new(&filename), false).unwrap().lines
+        };
+
+        assert_eq!(vec!["foo"], search(&["-o"]));
+        assert_eq!(vec!["1"], search(&["-oc"]));
+        assert_eq!(vec!["0123bar789foo"], search(&["--replace", "bar"]));
+        assert_eq!(vec!["\x1b[1;31mfoo\x1b[0m"], search(&["-o", "--color"]));
+        assert_eq!(vec!["0123\x1b[1;31mfoo\x1b[0m789foo"], search(&["--color"]));
+    }
+
+    /// Grouped short options test
+    ///
+    /// Test if a group of short options is split into its letters, with a value taken from the rest of the group
+    #[test]
+    fn grouped_short_options() {
+        let config = Config::new(&args(&["grep_remake", "-lrZ", "-nj4", "query", "dir"])).unwrap();
+
+        assert!(config.files_with_matches && config.null && config.line_number);
+        assert_eq!(Some(true), config.recursive);
+        assert_eq!(4, config.threads);
+        assert_eq!("query", config.query);
+    }
+
+    /// Count and only matching test
+    ///
+    /// Test if -c counts matching lines while -oc counts every occurrence
+    #[test]
+    fn count_only_matching() {
+        let filename = temp_file("count.txt", "one two one one\nnone here\nthree\n");
+
+        let config = Config::new(&args(&["grep_remake", "-c", "one", &filename])).unwrap();
+        assert_eq!(vec!["2"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "-c", "one t", &filename])).unwrap();
+        assert_eq!(vec!["1"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "-oc", "one", &filename])).unwrap();
+        assert_eq!(vec!["4"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "-on", "one", &filename])).unwrap();
+        assert_eq!(vec!["1:one", "1:one", "1:one", "2:one"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Occurrence count test
+    ///
+    /// Test if a line with three occurrences counts 1 with -c and 3 with -oc
+    #[test]
+    fn count_occurrences_on_one_line() {
+        let filename = temp_file("occurrences.txt", "cat cat cat\ndog\n");
+
+        let config = Config::new(&args(&["grep_remake", "-c", "cat", &filename])).unwrap();
+        assert_eq!(vec!["1"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "-oc", "cat", &filename])).unwrap();
+        assert_eq!(vec!["3"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Distinct count test
+    ///
+    /// Test if repeated matching lines count once with --count-distinct, and repeated matches once with -o
+    #[test]
+    fn count_distinct_lines() {
+        let filename = temp_file("distinct.log", "error: disk
+error: net
+error: disk
+ok
+error: disk
+");
+        let count = |options: &[&str]| {
+            let mut arguments = vec!["grep_remake"];
+            arguments.extend(options);
+            arguments.extend(["error", &filename]);
+            search_file(&Config::new(&args(&arguments)).unwrap(), &Target::new(&filename), false).unwrap().lines
+        };
+
+        assert_eq!(vec!["4"], count(&["-c"]));
+        assert_eq!(vec!["2"], count(&["--count-distinct"]));
+        assert_eq!(vec!["1"], count(&["--count-distinct", "-o"]));
+    }
+
+    /// Pattern count test
+    ///
+    /// Test if each pattern's matching lines are counted alone, a line with several counting for each
+    #[test]
+    fn count_per_pattern_lines() {
+        let filename = temp_file("per_pattern.log", "error: disk\nwarning: disk\nerror: net\nok\nerror and warning\n");
+        let patterns = ["-e", "error", "-e", "warning", "-e", "disk"];
+        let mut arguments = vec!["grep_remake", "--count-per-pattern"];
+        arguments.extend(patterns);
+        arguments.push(&filename);
+        let config = Config::new(&args(&arguments)).unwrap();
+
+        let lines = search_file(&config, &Target::new(&filename), false).unwrap().lines;
+        assert_eq!(vec!["error: 3", "warning: 2", "disk: 2"], lines);
+        assert_eq!(
+            vec![format!("{}:error: 3", filename), format!("{}:warning: 2", filename), format!("{}:disk: 2", filename)],
+            search_file(&config, &Target::new(&filename), true).unwrap().lines
+        );
+    }
+
+    /// Span test
+    ///
+    /// Test if --span gives each file's first and last matching lines and count, files without any only with --with-zero
+    #[test]
+    fn span_summaries() {
+        let first = temp_file("span_a.txt", "skip
+match
+skip
+match
+match
+skip
+match
+");
+        let second = temp_file("span_b.txt", "nothing
+");
+        let output = |options: &[&str]| {
+            let mut arguments = vec!["grep_remake", "--span"];
+            arguments.extend(options);
+            arguments.extend(["match", &first, &second]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(format!("{}: first=2 last=7 count=4\n", first), output(&[]));
+        assert_eq!(format!("{}: first=2 last=7 count=4\n{}: none\n", first, second), output(&["--with-zero"]));
+        assert_eq!("first=2 last=7 count=4", span_summary(&[(2, String::new()), (7, String::new())], 4));
+    }
+
+    /// Byte count test
+    ///
+    /// Test if --count-bytes totals the UTF-8 bytes of matching lines, or of the matches with -o
+    #[test]
+    fn count_matched_bytes() {
+        let first = temp_file("bytes_a.txt", "café match
+skip
+match
+");
+        let second = temp_file("bytes_b.txt", "nothing
+");
+        let output = |options: &[&str], files: &[&str]| {
+            let mut arguments = vec!["grep_remake"];
+            arguments.extend(options);
+            arguments.push("match");
+            arguments.extend(files);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        // "café match" is 10 characters but 11 bytes
+        assert_eq!("16\n", output(&["--count-bytes"], &[&first]));
+        assert_eq!("10\n", output(&["--count-bytes", "-o"], &[&first]));
+        assert_eq!(format!("{}:16\n", first), output(&["--count-bytes"], &[&first, &second]));
+    }
+
+    /// CSV output test
+    ///
+    /// Test if CSV output has a single header and quotes fields containing commas and quotes
+    #[test]
+    fn csv_output() {
+        let filename = temp_file("report.csv.txt", "plain match\nmatch, with \"quotes\"\nskip\n");
+        let config = Config::new(&args(&["grep_remake", "--format", "csv", "match", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+
+        assert_eq!(
+            format!("file,line_number,text\n{0},1,plain match\n{0},2,\"match, with \"\"quotes\"\"\"\n", filename),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    /// CSV count test
+    ///
+    /// Test if counts are written as file,count records under their own header
+    #[test]
+    fn csv_counts() {
+        let first = temp_file("csv_counts/a.txt", "match\nmatch\n");
+        let second = temp_file("csv_counts/b,c.txt", "match\n");
+        let config = Config::new(&args(&["grep_remake", "--format", "csv", "-c", "match", &first, &second])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("file,count\n{},2\n\"{}\",1\n", first, second), String::from_utf8(out).unwrap());
+    }
+
+    /// Markdown output test
+    ///
+    /// Test if Markdown output has a single header, escapes pipes and shortens long lines
+    #[test]
+    fn markdown_output() {
+        let filename = temp_file("table.md.txt", "a | b match\nskip\nmatch in the longest line\n");
+        let config = Config::new(&args(&["grep_remake", "--format", "markdown", "--max-columns", "12", "match", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+
+        assert_eq!(
+            format!(
+                "| File | Line | Match |\n| --- | --- | --- |\n| {0} | 1 | a \\| b match |\n| {0} | 3 | match in th… |\n",
+                filename
+            ),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    /// Column limit test
+    ///
+    /// Test if lines are cut to the width given, prefixes included, and left alone with 0 or when they fit
+    #[test]
+    fn max_columns() {
+        assert_eq!(None, column_cut("short", 5));
+        assert_eq!(Some(4), column_cut("longer", 5));
+        assert_eq!(Some(3), column_cut("héllo", 3));
+
+        let config = Config::new(&args(&["grep_remake", "-n", "--max-columns", "10", "match", "file"])).unwrap();
+        assert_eq!("7:a match…", format_line(&config, None, 7, "a match in a long line".to_string(), LineKind::Match, None));
+        assert_eq!("7:a match", format_line(&config, None, 7, "a match".to_string(), LineKind::Match, None));
+
+        let config = Config { max_columns: Some(0), ..config };
+        assert_eq!("7:a match in a long line", format_line(&config, None, 7, "a match in a long line".to_string(), LineKind::Match, None));
+    }
+
+    /// Chunked search test
+    ///
+    /// Test if a file searched in chunks on several threads gives exactly the output of a sequential search
+    #[test]
+    fn chunked_search() {
+        let contents: String = (1..=500)
+            .map(|number| format!("line {} {}{}\n", number, "x".repeat(number % 37), if number % 7 == 0 { " match" } else { "" }))
+            .collect();
+        let filename = temp_file("chunked.txt", &format!("{}last match without newline", contents));
+
+        let output = |options: &[&str]| {
+            let mut arguments = vec!["grep_remake", "-n"];
+            arguments.extend(options);
+            arguments.extend(["match", &filename]);
+            search_file(&Config::new(&args(&arguments)).unwrap(), &Target::new(&filename), false).unwrap()
+        };
+
+        let sequential = output(&["-j", "1", "--chunk-size", "100"]);
+        let chunked = output(&["-j", "4", "--chunk-size", "100"]);
+        assert_eq!(72, sequential.lines.len());
+        assert_eq!("501:last match without newline", sequential.lines[71]);
+        assert_eq!(sequential.lines, chunked.lines);
+        assert_eq!(output(&["-c", "-j", "1"]).lines, output(&["-c", "-j", "3", "--chunk-size", "64"]).lines);
+    }
+
+    /// Per file limit test
+    ///
+    /// Test if a noisy file is cut short with a note while other files are unaffected, within the total limit
+    #[test]
+    fn per_file_max() {
+        let noisy = temp_file("noisy.txt", &"match\n".repeat(20));
+        let quiet = temp_file("quiet.txt", "match\n");
+        let config = Config::new(&args(&["grep_remake", "--max-per-file", "3", "--truncation-note", "match", &noisy, &quiet])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(
+            format!("{0}:match\n{0}:match\n{0}:match\n{0}:... (truncated)\n{1}:match\n", noisy, quiet),
+            String::from_utf8(out).unwrap()
+        );
+
+        let config = Config::new(&args(&["grep_remake", "--max-per-file", "3", "--max-total", "2", "match", &noisy, &quiet])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{0}:match\n{0}:match\n", noisy), String::from_utf8(out).unwrap());
+    }
+
+    /// Inverted limit test
+    ///
+    /// Test if -v -m counts the lines printed rather than the lines containing the query
+    #[test]
+    fn invert_max_count() {
+        let filename = temp_file("invert_max.txt", "one
+two
+three
+error
+four
+error
+five
+");
+        let config = Config::new(&args(&["grep_remake", "-v", "-m", "3", "error", &filename])).unwrap();
+        assert_eq!(vec!["one", "two", "three"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let filename = temp_file("invert_max_late.txt", "error
+one
+error
+error
+two
+three
+four
+");
+        let config = Config::new(&args(&["grep_remake", "-v", "-m3", "error", &filename])).unwrap();
+        assert_eq!(vec!["one", "two", "three"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Inferred recursion test
+    ///
+    /// Test if a sole directory argument is searched recursively unless --no-recursive is given
+    #[test]
+    fn infer_recursion_for_directory() {
+        let first = temp_file("infer/one.txt", "match\n");
+        let dir = Path::new(&first).parent().unwrap().to_string_lossy().into_owned();
+        let second = temp_file("infer/nested/two.txt", "match\n");
+
+        let config = Config::new(&args(&["grep_remake", "match", &dir])).unwrap();
+        assert_eq!(None, config.recursive);
+        let expected = vec![
+            Target { path: second.clone(), display: "nested/two.txt".to_string(), duplicates: Vec::new() },
+            Target { path: first.clone(), display: "one.txt".to_string(), duplicates: Vec::new() },
+        ];
+        assert_eq!((expected, 0), collect_files(&config, &mut io::sink()).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "--no-recursive", "match", &dir])).unwrap();
+        assert_eq!((Vec::new(), 1), collect_files(&config, &mut io::sink()).unwrap());
+    }
+
+    /// File type test
+    ///
+    /// Test if -t keeps only the walked files of that type, and an unknown type is an error listing the known ones
+    #[test]
+    fn walk_file_types() {
+        let first = temp_file("types/src/lib.rs", "match\n");
+        let dir = Path::new(&first).parent().unwrap().parent().unwrap().to_string_lossy().into_owned();
+        temp_file("types/README.md", "match\n");
+        temp_file("types/src/build.py", "match\n");
+
+        let config = Config::new(&args(&["grep_remake", "-t", "rust", "match", &dir])).unwrap();
+        let expected = vec![Target { path: first.clone(), display: "src/lib.rs".to_string(), duplicates: Vec::new() }];
+        assert_eq!((expected, 0), collect_files(&config, &mut io::sink()).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "--type-not", "py", "-T", "md", "match", &dir])).unwrap();
+        assert_eq!(1, collect_files(&config, &mut io::sink()).unwrap().0.len());
+
+        let error = Config::new(&args(&["grep_remake", "-t", "cobol", "match", &dir])).err().unwrap().to_string();
+        assert!(error.starts_with("Unknown file type cobol, known types are: c, cpp,"));
+    }
+
+    /// Estimate test
+    ///
+    /// Test if the matches of a seeded sample of files are scaled up to all of them
+    #[test]
+    fn estimated_matches() {
+        let files: Vec<String> = (0..4).map(|index| temp_file(&format!("estimate/{}.txt", index), "match\nskip\nmatch\n")).collect();
+        let mut arguments = vec!["grep_remake", "--estimate", "50", "--seed", "42", "match"];
+        arguments.extend(files.iter().map(String::as_str));
+
+        let mut out = Vec::new();
+        assert_eq!(0, run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap());
+        assert_eq!("~8 matches (from 2 of 4 files, seed 42)\n", String::from_utf8(out).unwrap());
+        assert!(Config::new(&args(&["grep_remake", "--estimate", "0", "match", "file"])).is_err());
+    }
+
+    /// Path substring test
+    ///
+    /// Test if only walked files with the text in their path are searched, ignoring case with -i
+    #[test]
+    fn walk_path_substring() {
+        let first = temp_file("path_contains/tests/parse.rs", "match\n");
+        let dir = Path::new(&first).parent().unwrap().parent().unwrap().to_string_lossy().into_owned();
+        let second = temp_file("path_contains/src/TestUtil.rs", "match\n");
+        temp_file("path_contains/src/lib.rs", "match\n");
+
+        let walked = |options: &[&str]| {
+            let mut list = vec!["grep_remake"];
+            list.extend(options);
+            list.extend(["match", &dir]);
+            let (files, _) = collect_files(&Config::new(&args(&list)).unwrap(), &mut io::sink()).unwrap();
+            files.into_iter().map(|target| target.path).collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec![first.clone()], walked(&["--case-sensitive", "--path-contains", "test"]));
+        assert_eq!(vec![second, first], walked(&["-i", "--path-contains", "test"]));
+    }
+
+    /// Debug test
+    ///
+    /// Test if --debug gives each file's outcome on the error stream, with the option that skipped it
+    #[test]
+    fn debug_decisions() {
+        let first = temp_file("debug/keep.rs", "match\nmatch\n");
+        let dir = Path::new(&first).parent().unwrap().to_string_lossy().into_owned();
+        temp_file("debug/vendor.rs", "match\n");
+        temp_file("debug/notes.md", "match\n");
+        let binary = temp_file("debug/data.rs", "");
+        fs::write(&binary, b"\0match\n").unwrap();
+
+        let config = Config::new(&args(&["grep_remake", "--debug", "-I", "-t", "rust", "--exclude", "vendor.rs", "match", &dir])).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        run(config, &mut out, &mut err).unwrap();
+        let err = String::from_utf8(err).unwrap();
+
+        assert!(err.contains("grep_remake: debug: notes.md: skipped (--type)\n"), "{}", err);
+        assert!(err.contains("grep_remake: debug: vendor.rs: skipped (--exclude)\n"), "{}", err);
+        assert!(err.contains("grep_remake: debug: data.rs: skipped (binary)\n"), "{}", err);
+        assert!(err.contains("grep_remake: debug: keep.rs: searched (2 matches)\n"), "{}", err);
+        assert!(!String::from_utf8(out).unwrap().contains("debug"));
+    }
+
+    /// Walked exclude test
+    ///
+    /// Test if anchored --exclude globs and their `!` re-includes apply to paths below the directory walked
+    #[test]
+    fn exclude_walked_paths() {
+        let kept = temp_file("exclude_walk/proj/src/main.rs", "match\n");
+        temp_file("exclude_walk/proj/vendor/a.txt", "match\n");
+        temp_file("exclude_walk/proj/vendor/keep/b.txt", "match\n");
+        let root = Path::new(&kept).parent().unwrap().parent().unwrap().to_string_lossy().into_owned();
+
+        let arguments = ["grep_remake", "-r", "-l", "--exclude", "vendor/**", "--exclude", "!vendor/keep/**", "match", &root];
+        let mut out = Vec::new();
+        run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+        assert_eq!("src/main.rs\nvendor/keep/b.txt\n", String::from_utf8(out).unwrap());
+    }
+
+    /// Structured output conflict test
+    ///
+    /// Test if counts and stats, which aren't rows, are refused with the formats that only hold rows
+    #[test]
+    fn structured_output_conflicts() {
+        let refused = |extra: &[&str]| {
+            let mut arguments = vec!["grep_remake"];
+            arguments.extend_from_slice(extra);
+            arguments.extend_from_slice(&["query", "file"]);
+            Config::new(&args(&arguments)).is_err()
+        };
+
+        assert!(refused(&["--format", "json-array", "-c"]));
+        assert!(refused(&["--format", "json-array", "--stats"]));
+        assert!(refused(&["--format", "csv", "--stats"]));
+        assert!(refused(&["--format", "markdown", "--stats"]));
+        assert!(!refused(&["--format", "json-array", "-n"]));
+        assert!(!refused(&["--format", "json", "--stats"]));
+    }
+
+    /// Numeric sort test
+    ///
+    /// Test if --sort-numeric orders lines by the number they start with, where text order would put 10 before 9
+    #[test]
+    fn sort_numeric() {
+        let filename = temp_file("sort_numeric/ids.log", "10 late id\nno id\n9 early id\n-2 negative id\n100 last id\n");
+        let sorted = |extra: &[&str]| {
+            let mut arguments = vec!["grep_remake", "--sort-numeric"];
+            arguments.extend_from_slice(extra);
+            arguments.extend_from_slice(&["id", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("-2 negative id\n9 early id\n10 late id\n100 last id\nno id\n", sorted(&[]));
+        assert_eq!("no id\n-2 negative id\n9 early id\n10 late id\n100 last id\n", sorted(&["--non-numeric-first"]));
+        assert_eq!(None, leading_number("99999999999999999999 too big"));
+        assert!(Config::new(&args(&["grep_remake", "--non-numeric-first", "id", &filename])).is_err());
+    }
+
+    /// Per file timeout test
+    ///
+    /// Test if a search that has used up its time stops at the next check, keeping the matches before it
+    #[test]
+    fn per_file_timeout() {
+        let started = Instant::now();
+        assert!(!over_budget(None, TIMEOUT_CHECK_LINES, started));
+        assert!(!over_budget(Some(Duration::from_secs(60)), TIMEOUT_CHECK_LINES, started));
+        assert!(!over_budget(Some(Duration::ZERO), TIMEOUT_CHECK_LINES - 1, started));
+        assert!(over_budget(Some(Duration::ZERO), TIMEOUT_CHECK_LINES, started));
+
+        // With no time at all only the lines before the first check are searched
+        let contents = "match\n".repeat(TIMEOUT_CHECK_LINES * 3);
+        let mut config = Config::new(&args(&["grep_remake", "--per-file-timeout", "500", "match", "file.txt"])).unwrap();
+        config.per_file_timeout = Some(Duration::ZERO);
+        let scan = search_stream(&config, contents.as_bytes()).unwrap();
+        assert!(scan.timed_out);
+        assert_eq!(TIMEOUT_CHECK_LINES, scan.matches.len());
+
+        assert_eq!(
+            Some("grep_remake: slow.txt: stopped after 0ms, the rest of it wasn't searched".to_string()),
+            timeout_warning(&config, "slow.txt", true)
+        );
+        assert!(Config::new(&args(&["grep_remake", "--per-file-timeout", "0", "match", "file.txt"])).is_err());
+    }
+
+    /// Search groups test
+    ///
+    /// Test if each --search is run on its own --in files and printed under its own label
+    #[test]
+    fn labelled_searches() {
+        let first = temp_file("searches/a.txt", "foo here\nbar here\n");
+        let second = temp_file("searches/b.txt", "no foo\nbar there\n");
+        let config = Config::new(&args(&["grep_remake", "-n", "--search", "foo", "--in", &first, "--search", "bar", "--in", &second])).unwrap();
+        assert_eq!(vec![first.clone()], config.searches[0].filenames);
+
+        let mut out = Vec::new();
+        assert_eq!(0, run(config, &mut out, &mut io::sink()).unwrap());
+        assert_eq!("==> foo <==\n1:foo here\n\n==> bar <==\n2:bar there\n", String::from_utf8(out).unwrap());
+
+        assert!(Config::new(&args(&["grep_remake", "--in", &first, "--search", "foo"])).is_err());
+        assert!(Config::new(&args(&["grep_remake", "--search", "foo", "--search", "bar", "--in", &second])).is_err());
+        // Files and patterns outside the groups would be left unsearched
+        assert!(Config::new(&args(&["grep_remake", "--search", "foo", "--in", &first, &second])).is_err());
+        assert!(Config::new(&args(&["grep_remake", "-e", "bar", "--search", "foo", "--in", &first])).is_err());
+    }
+
+    /// Inferred file search test
+    ///
+    /// Test if a sole file argument is searched on its own, without a filename prefix
+    #[test]
+    fn infer_no_recursion_for_file() {
+        let filename = temp_file("infer_file.txt", "match\n");
+        let config = Config::new(&args(&["grep_remake", "match", &filename])).unwrap();
+
+        assert!(!recurses(&config));
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!("match\n", String::from_utf8(out).unwrap());
+    }
+
+    /// Unique matches test
+    ///
+    /// Test if each distinct match is printed once across files, in first-appearance order
+    #[test]
+    fn unique_matches() {
+        let first = temp_file("unique_first.log", "ERR: disk\nok\nErr: net, ERR: disk\n");
+        let second = temp_file("unique_second.log", "err: user\nErr: net\n");
+        let config = Config::new(&args(&["grep_remake", "--unique-matches", "err", &first, &second])).unwrap();
+
+        // Case insensitive, so each spelling is a distinct match
+        let mut out = Vec::new();
+        run(Config { case_sensitive: false, ..config }, &mut out, &mut io::sink()).unwrap();
+        assert_eq!("ERR\nErr\nerr\n", String::from_utf8(out).unwrap());
+    }
+
+    /// Denominator test
+    ///
+    /// Test if each file reports its matches over the lines containing the denominator pattern
+    #[test]
+    fn denominator_density() {
+        let filename = temp_file("denominator.log", "GET /a 500\nGET /b 200\nGET /c 500\nGET /d 200\nget /e 200\nnoise\n");
+        let config = Config::new(&args(&["grep_remake", "--denominator", "GET", "500", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!("2/4\n", String::from_utf8(out).unwrap());
+
+        // The denominator follows the same case setting as the query
+        let config = Config::new(&args(&["grep_remake", "--denominator", "GET", "500", &filename])).unwrap();
+        let mut out = Vec::new();
+        run(Config { case_sensitive: false, ..config }, &mut out, &mut io::sink()).unwrap();
+        assert_eq!("2/5\n", String::from_utf8(out).unwrap());
+    }
+
+    /// Format template test
+    ///
+    /// Test if a template is expanded for each match and an unknown placeholder is a config error
+    #[test]
+    fn format_template() {
+        let filename = temp_file("template.rs", "fn main() {\n    let x = x + 1;\n}\n");
+        let config =
+            Config::new(&args(&["grep_remake", "--format-template", "{path}:{line}:{col}: {match}", "x", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{0}:2:9: x\n{0}:2:13: x\n", filename), String::from_utf8(out).unwrap());
+
+        assert!(Config::new(&args(&["grep_remake", "--format-template", "{file}", "x", &filename])).is_err());
+    }
+
+    /// Preprocessor test
+    ///
+    /// Test if the preprocessor's output is searched and a failing preprocessor only skips its file
+    #[cfg(unix)]
+    #[test]
+    fn preprocessor() {
+        let filename = temp_file("pre.txt", "plain\nneedle here\n");
+        let config = Config::new(&args(&["grep_remake", "--pre", "cat", "needle", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(0, run(config, &mut out, &mut io::sink()).unwrap());
+        assert_eq!("needle here\n", String::from_utf8(out).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "--pre", "false", "needle", &filename])).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(2, run(config, &mut out, &mut err).unwrap());
+        assert!(out.is_empty());
+        assert!(String::from_utf8(err).unwrap().starts_with(&format!("grep_remake: {}: preprocessor failed", filename)));
+    }
+
+    /// No context test
+    ///
+    /// Test if lines far apart get no `--` between them when no context was asked for
+    #[test]
+    fn no_separator_without_context() {
+        let filename = temp_file("no_context.txt", "hit\nmiss\nhit\n");
+        let config = Config::new(&args(&["grep_remake", "hit", &filename])).unwrap();
+
+        assert_eq!(vec!["hit", "hit"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Marked context test
+    ///
+    /// Test if only matched lines get the marker within their context, with `--` between groups
+    #[test]
+    fn mark_matches_in_context() {
+        let filename = temp_file("context.txt", "one\ntwo\nneedle\nfour\nfive\nsix\nseven\nneedle\n");
+        let config = Config::new(&args(&["grep_remake", "-n", "-C", "1", "--mark-matches", "needle", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(
+            "  2-two\n> 3:needle\n  4-four\n--\n  7-seven\n> 8:needle\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    /// Per-file case test
+    ///
+    /// Test if only files matching --ignore-case-files are searched case insensitively
+    #[test]
+    fn ignore_case_files() {
+        let settings = temp_file("case/settings.ini", "Debug = true\n");
+        let code = temp_file("case/main.rs", "let Debug = true;\n");
+        let config =
+            Config::new(&args(&["grep_remake", "--ignore-case-files", "*.ini", "debug", &settings, &code])).unwrap();
+
+        let mut out = Vec::new();
+        run(Config { case_sensitive: true, ..config }, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{}:Debug = true\n", settings), String::from_utf8(out).unwrap());
+    }
+
+    /// Invert match test
+    ///
+    /// Test if -v selects the lines without the query, keeping their line numbers
+    #[test]
+    fn invert_match() {
+        let config = Config::new(&args(&["grep_remake", "-v", "error", "memory"])).unwrap();
+        let contents = "error one\nfine\nerror two\nalso fine\n";
+
+        assert_eq!(
+            vec![(2, "fine".to_string()), (4, "also fine".to_string())],
+            search_config_reader(&config, contents.as_bytes()).unwrap()
+        );
+    }
+
+    /// Sort order test
+    ///
+    /// Test if --sort path orders files by name and --sort count puts the most matches first
+    #[test]
+    fn sort_files() {
+        let one = temp_file("sort/c_one.txt", "hit\n");
+        let three = temp_file("sort/a_three.txt", "hit\nhit\nhit\n");
+        let two = temp_file("sort/b_two.txt", "hit\nmiss\nhit\n");
+
+        let config = Config::new(&args(&["grep_remake", "-c", "--sort", "path", "hit", &one, &three, &two])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{}:3\n{}:2\n{}:1\n", three, two, one), String::from_utf8(out).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "-l", "--sort", "count", "hit", &one, &two, &three])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{}\n{}\n{}\n", three, two, one), String::from_utf8(out).unwrap());
+    }
+
+    /// Alternatives test
+    ///
+    /// Test if a line containing only the middle alternative is found, with any delimiter
+    #[test]
+    fn alternatives() {
+        let config = Config::new(&args(&["grep_remake", "--alternatives", "foo|bar|baz", "memory"])).unwrap();
+        let contents = "nothing\nonly bar here\nfoo|bar|baz\n";
+        assert_eq!(
+            vec![(2, "only bar here".to_string()), (3, "foo|bar|baz".to_string())],
+            search_config_reader(&config, contents.as_bytes()).unwrap()
+        );
+
+        let config = Config::new(&args(&["grep_remake", "--alternatives-delimiter", ",", "foo,bar", "memory"])).unwrap();
+        assert_eq!(vec![0..3, 7..10], find_patterns(&config, "bar of foo"));
+    }
+
+    /// Stats test
+    ///
+    /// Test if --stats reports the bytes of matched text per file and in total
+    #[test]
+    fn stats_matched_bytes() {
+        let first = temp_file("stats/first.txt", "an error\nfine\nerror again\n");
+        let second = temp_file("stats/second.txt", "nothing\n");
+        let third = temp_file("stats/third.txt", "error\n");
+        let config = Config::new(&args(&["grep_remake", "--stats", "error", &first, &second, &third])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        let expected = format!(
+            "\n{}: 19 bytes matched\n{}: 5 bytes matched\n3 files searched, 2 with matches, 3 matches, 24 bytes matched\n",
+            first, third
+        );
+        assert!(String::from_utf8(out).unwrap().ends_with(&expected));
+    }
+
+    /// Match context test
+    ///
+    /// Test if a long line is cut to a window either side of each match, windows far apart
+    /// being printed separately and close ones joined
+    #[test]
+    fn match_context_windows() {
+        let line = format!("{}needle{}needle{}", "a".repeat(40), "b".repeat(50), "c".repeat(3));
+        let windowed = match_windows(&line, &find_all("needle", &line, true), 5);
+        assert_eq!("…aaaaaneedlebbbbb…bbbbbneedleccc", windowed);
+        assert_eq!("x needle y needle z", match_windows("x needle y needle z", &[2..8, 11..17], 3));
+        assert_eq!("…é needle é…", match_windows("ééé needle ééé", &find_all("needle", "ééé needle ééé", true), 2));
+        assert_eq!("no match", match_windows("no match", &[], 5));
+
+        let filename = temp_file("match_context/minified.js", &format!("{}\n", line));
+        let config = Config::new(&args(&["grep_remake", "--match-context", "5", "-n", "needle", &filename])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("1:{}\n", windowed), String::from_utf8(out).unwrap());
+    }
+
+    /// Balance test
+    ///
+    /// Test if only the balanced brackets around a match are printed, from the default set or the one given
+    #[test]
+    fn balanced_match_spans() {
+        let filename = temp_file("balance/calls.rs", "let total = sum(values.iter().map(|v| scale(v, factor)), 0);\nVec<Option<factor>>\n");
+        let output = |options: &[&str]| {
+            let mut list = vec!["grep_remake"];
+            list.extend(options);
+            list.extend(["factor", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&list)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        // The generic line has no round or square brackets, so it is printed whole
+        assert_eq!("…(v, factor)…\nVec<Option<factor>>\n", output(&["--balance"]));
+        assert_eq!("let total = sum(values.iter().map(|v| scale(v, factor)), 0);\n…<factor>…\n", output(&["--balance-brackets", "<>"]));
+        // Each match has its own brackets
+        assert_eq!("…(a)…(a, b)", balanced_spans("f(a) + g(a, b)", &find_all("a", "f(a) + g(a, b)", true), &[('(', ')')]));
+        assert!(Config::new(&args(&["grep_remake", "--balance-brackets", "(", "factor", "file"])).is_err());
+    }
+
+    /// Replace test
+    ///
+    /// Test if --replace prints matched lines with their matches replaced, reading escapes with --escape
+    #[test]
+    fn replaced_output() {
+        let filename = temp_file("replace/names.txt", "old name and old value\nnothing\n");
+        let output = |options: &[&str]| {
+            let mut list = vec!["grep_remake"];
+            list.extend(options);
+            list.extend(["old", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&list)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("new name and new value\n", output(&["--replace", "new"]));
+        assert_eq!("\t name and \t value\n", output(&["--escape", "--replace", "\\t"]));
+        assert!(Config::new(&args(&["grep_remake", "--in-place", "old", &filename])).is_err());
+    }
+
+    /// Trim test
+    ///
+    /// Test if --trim removes indentation from the printed text only, after the prefixes
+    #[test]
+    fn trimmed_output() {
+        let filename = temp_file("trim/indented.rs", "fn main() {\n    let total = 1;  \n\tlet totally = 2;\n}\n");
+        let config = Config::new(&args(&["grep_remake", "--trim", "-n", "-H", "let total", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{0}:2:let total = 1;\n{0}:3:let totally = 2;\n", filename), String::from_utf8(out).unwrap());
+
+        // The indentation is still there to be matched
+        let config = Config::new(&args(&["grep_remake", "--trim", "    let", &filename])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!("let total = 1;\n", String::from_utf8(out).unwrap());
+    }
+
+    /// Query stdin test
+    ///
+    /// Test if a query read from a reader is shared by searches of two buffers on separate threads
+    #[test]
+    fn query_from_reader() {
+        let mut config = Config::new(&args(&["grep_remake", "--query-stdin", "-j", "2", "first", "second"])).unwrap();
+        assert_eq!(vec!["first", "second"], config.filenames);
+        config.read_query(io::Cursor::new("needle\n")).unwrap();
+        assert_eq!("needle", config.query);
+
+        let buffers = ["hay\nneedle\n", "a needle\nhay\nneedles\n"];
+        let found: Vec<Vec<usize>> = thread::scope(|scope| {
+            let workers: Vec<_> = buffers
+                .iter()
+                .map(|buffer| {
+                    let config = &config;
+                    scope.spawn(move || search_config_reader(config, buffer.as_bytes()).unwrap())
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().unwrap().into_iter().map(|(number, _)| number).collect()).collect()
+        });
+        assert_eq!(vec![vec![2], vec![1, 3]], found);
+
+        assert!(config.read_query(io::Cursor::new("\n")).is_err());
+        assert!(Config::new(&args(&["grep_remake", "--query-stdin", "--files-from", "-"])).is_err());
+    }
+
+    /// By extension test
+    ///
+    /// Test if matches are totalled per extension, most first, with extensionless files as (none)
+    #[test]
+    fn matches_by_extension() {
+        let first = temp_file("by_extension/main.rs", "error
+error
+");
+        let second = temp_file("by_extension/lib.rs", "error
+");
+        let third = temp_file("by_extension/notes.md", "error
+fine
+");
+        let fourth = temp_file("by_extension/Makefile", "fine
+");
+        let config = Config::new(&args(&["grep_remake", "--by-extension", "-c", "error", &first, &second, &third, &fourth])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("\n.rs: 3, .md: 1\n"));
+
+        let config = Config::new(&args(&["grep_remake", "--by-extension", "fine", &third, &fourth])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("\n(none): 1, .md: 1\n"));
+    }
+
+    /// Long line test
+    ///
+    /// Test if a line far past --max-line-bytes is cut rather than held whole, or is an error with --long-lines error
+    #[test]
+    fn max_line_bytes() {
+        // Ten million bytes before the first newline, made as they are read rather than held anywhere
+        let input = || BufReader::new(io::repeat(b'a').take(10_000_000).chain(&b"needle\nnext needle\n"[..]));
+
+        let mut line = Vec::new();
+        let mut reader = input();
+        assert_eq!((10_000_007, true), read_line_capped(&mut reader, &mut line, 1024).unwrap());
+        assert_eq!(1025, line.len());
+        assert!(line.capacity() < 64 * 1024);
+        line.clear();
+        assert_eq!((12, false), read_line_capped(&mut reader, &mut line, 1024).unwrap());
+        assert_eq!(b"next needle\n", &line[..]);
+
+        // The needle past the limit isn't seen, but the next line is still found
+        let config = Config::new(&args(&["grep_remake", "-n", "--max-line-bytes", "1024", "needle", "file"])).unwrap();
+        assert_eq!(vec![(2, "next needle".to_string())], search_config_reader(&config, input()).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "--max-line-bytes", "1024", "--long-lines", "error", "needle", "file"])).unwrap();
+        let error = search_config_reader(&config, input()).unwrap_err();
+        assert_eq!("line 1 is longer than 1024 bytes", error.to_string());
+
+        // A character the limit splits is left out
+        let mut line = Vec::new();
+        read_line_capped(&mut "aé\n".as_bytes(), &mut line, 2).unwrap();
+        assert_eq!(b"a\n", &line[..]);
+    }
+
+    /// JSON summary test
+    ///
+    /// Test if --stats with JSON output ends with a summary record of the run after the match records
+    #[test]
+    fn json_summary_record() {
+        let first = temp_file("json_summary/first.txt", "an error\nfine\nerror again\n");
+        let second = temp_file("json_summary/second.txt", "nothing\n");
+        let config = Config::new(&args(&["grep_remake", "--json-summary", "error", &first, &second])).unwrap();
+        assert!(config.stats && config.format == OutputFormat::Json);
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let records: Vec<&str> = out.lines().collect();
+        assert_eq!(3, records.len());
+        assert!(records[..2].iter().all(|record| record.starts_with("{\"path\":") && !record.contains("\"type\"")));
+
+        let summary = records[2];
+        let prefix = "{\"type\":\"summary\",\"files_searched\":2,\"files_matched\":1,\"matches\":2,\"bytes_matched\":19,\"elapsed_ms\":";
+        assert!(summary.starts_with(prefix) && summary.ends_with('}'), "{}", summary);
+        assert!(summary[prefix.len()..summary.len() - 1].parse::<u128>().is_ok());
+    }
+
+    /// Path separator test
+    ///
+    /// Test if both kinds of separator in printed paths are rewritten to the chosen one
+    #[test]
+    fn path_separator() {
+        let config = Config::new(&args(&["grep_remake", "--path-separator", "/", "query", "a.txt"])).unwrap();
+        assert_eq!("src/sub/file.txt", display_path(&config, "src\\sub/file.txt"));
+
+        let config = Config::new(&args(&["grep_remake", "--path-separator", "\\", "query", "a.txt"])).unwrap();
+        assert_eq!("src\\sub\\file.txt", display_path(&config, "src/sub\\file.txt"));
+
+        assert!(Config::new(&args(&["grep_remake", "--path-separator", "//", "query", "a.txt"])).is_err());
+    }
+
+    /// Compiled query test
+    ///
+    /// Test if a compiled query matches lines regardless of case, including after a longer line
+    #[test]
+    fn compiled_query() {
+        let query = CompiledQuery::new("dUcT");
+
+        assert!(query.matches_line("safe, fast, proDUCTive."));
+        assert!(!query.matches_line("a much longer line that never contains the query at all"));
+        assert!(query.matches_line("Duct tape."));
+        assert!(!query.matches_line("Trust me."));
+    }
+
+    /// Occurrence prefix test
+    ///
+    /// Test if matched lines are prefixed with their number of occurrences, optionally leaving out single ones
+    #[test]
+    fn occurrence_count() {
+        let filename = temp_file("occurrences.txt", "a to-do: do it, do it now\ndo once\n");
+        let config = Config::new(&args(&["grep_remake", "-n", "--occurrence-count", "do", &filename])).unwrap();
+        assert_eq!(
+            vec!["1:(x3): a to-do: do it, do it now", "2:(x1): do once"],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+
+        let config = Config::new(&args(&["grep_remake", "-n", "--omit-single-occurrence", "do", &filename])).unwrap();
+        assert_eq!(
+            vec!["1:(x3): a to-do: do it, do it now", "2:do once"],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+    }
+
+    /// Binary files test
+    ///
+    /// Test each way of handling a file containing a NUL byte
+    #[test]
+    fn binary_files() {
+        // Not valid UTF-8 either, which binary files needn't be
+        let filename = temp_file("binary.dat", "");
+        fs::write(&filename, b"header\0\xff needle\nmore needle\n").unwrap();
+        let search = |options: &[&str]| {
+            let mut list = vec!["grep_remake"];
+            list.extend(options);
+            list.extend(["needle", &filename]);
+            let config = Config::new(&args(&list)).unwrap();
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        };
+
+        assert_eq!(vec![format!("Binary file {} matches", filename)], search(&["--binary-files=binary"]));
+        assert!(search(&["--binary-files=without-match"]).is_empty());
+        assert_eq!(vec!["header\0\u{fffd} needle", "more needle"], search(&["--binary-files=text", "--lossy"]));
+
+        // Binary is the default
+        let config = Config::new(&args(&["grep_remake", "needle", &filename])).unwrap();
+        assert_eq!(BinaryMode::Binary, config.binary_mode);
+        assert!(Config::new(&args(&["grep_remake", "-n=3", "needle", &filename])).is_err());
+        assert!(Config::new(&args(&["grep_remake", "--line-number=3", "needle", &filename])).is_err());
+    }
+
+    /// Binary count test
+    ///
+    /// Test if --binary-count reports every occurrence in a binary file, and nothing for one without any
+    #[test]
+    fn binary_match_count() {
+        let matching = temp_file("binary_count/matching.bin", "");
+        fs::write(&matching, b"\x7fELF\0\0needle\xfe\xffneedle\0needle\nno\n").unwrap();
+        let other = temp_file("binary_count/other.bin", "");
+        fs::write(&other, b"\0\x01\x02nothing here\n").unwrap();
+        let search = |filename: &str| {
+            let config = Config::new(&args(&["grep_remake", "--binary-count", "needle", filename])).unwrap();
+            search_file(&config, &Target::new(filename), false).unwrap().lines
+        };
+
+        assert_eq!(vec![format!("Binary file {}: 3 matches", matching)], search(&matching));
+        assert!(search(&other).is_empty());
+    }
+
+    /// Display limit test
+    ///
+    /// Test if --limit stops printing but still counts every remaining match for the summary
+    #[test]
+    fn display_limit() {
+        let first = temp_file("limit/first.txt", "hit 1\nhit 2\nmiss\nhit 3\n");
+        let second = temp_file("limit/second.txt", "hit 4\nhit 5\n");
+        let config = Config::new(&args(&["grep_remake", "--limit", "2", "hit", &first, &second])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(
+            format!("{0}:hit 1\n{0}:hit 2\n... and 3 more matches\n", first),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    /// Pattern labels test
+    ///
+    /// Test if a line is labelled with every -e pattern it contains
+    #[test]
+    fn label_patterns() {
+        let filename = temp_file("labels.txt", "foo and bar\nonly bar\nneither\n");
+        let config =
+            Config::new(&args(&["grep_remake", "--label-patterns", "-e", "foo", "-e", "bar", &filename])).unwrap();
+        assert_eq!(vec![filename.clone()], config.filenames);
+
+        assert_eq!(
+            vec!["[foo,bar] foo and bar", "[bar] only bar"],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+    }
+
+    /// No filename test
+    ///
+    /// Test if -h leaves the filename off lines from several files, keeping the line numbers, and -H adds it for one
+    #[test]
+    fn no_filename() {
+        let first = temp_file("no_filename/first.txt", "match one\n");
+        let second = temp_file("no_filename/second.txt", "skip\nmatch two\n");
+
+        let config = Config::new(&args(&["grep_remake", "-h", "-n", "match", &first, &second])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!("1:match one\n2:match two\n", String::from_utf8(out).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "-H", "match", &first])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{}:match one\n", first), String::from_utf8(out).unwrap());
+    }
+
+    /// Duplicate files test
+    ///
+    /// Test if a file identical to an earlier one is reported as its duplicate rather than searched again
+    #[test]
+    fn dedup_files() {
+        let original = temp_file("dedup/original.txt", "a match\nother\n");
+        let copy = temp_file("dedup/copy.txt", "a match\nother\n");
+        let different = temp_file("dedup/different.txt", "a match\n");
+        let config = Config::new(&args(&["grep_remake", "--dedup-files", "match", &original, &copy, &different])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(
+            format!("{0}:a match\n{1}: duplicate of {0}\n{2}:a match\n", original, copy, different),
+            String::from_utf8(out).unwrap()
+        );
+
+        // Files of the same length are only folded together when every byte is the same
+        let same_length = temp_file("dedup/same_length.txt", "a catch\nother\n");
+        let unique = super::dedup_files(vec![Target::new(&original), Target::new(&same_length), Target::new(&copy)], &mut io::sink()).unwrap();
+        assert_eq!(vec![original.clone(), same_length], unique.iter().map(|target| target.path.clone()).collect::<Vec<_>>());
+        assert_eq!(vec![copy], unique[0].duplicates);
+    }
+
+    /// Lossy UTF-8 test
+    ///
+    /// Test if an invalid byte fails the file by default and is replaced with --lossy
+    #[test]
+    fn lossy_utf8() {
+        let filename = temp_file("lossy.txt", "");
+        fs::write(&filename, b"caf\xe9 menu\nmatch here\n").unwrap();
+
+        let config = Config::new(&args(&["grep_remake", "match", &filename])).unwrap();
+        let error = search_target(&config, &Target::new(&filename), false).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        // Only that file is skipped, with a warning naming it
+        let output = search_file(&config, &Target::new(&filename), false).unwrap();
+        assert!(output.lines.is_empty());
+        assert!(output.warning.unwrap().starts_with(&format!("grep_remake: {}: ", filename)));
+
+        let config = Config::new(&args(&["grep_remake", "--lossy", "caf", &filename])).unwrap();
+        assert_eq!(vec!["caf\u{fffd} menu"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Search count test
+    ///
+    /// Test if the counting functions agree with the searches that collect the lines
+    #[test]
+    fn search_count_agrees() {
+        let contents = "Rust:\nsafe, fast, productive.\nPick three.\nTrust me, rust is rusty.\n";
+
+        assert_eq!(search("rust", contents).len(), search_count("rust", contents, true));
+        assert_eq!(search_case_insensitive("rust", contents).len(), search_count("rust", contents, false));
+        assert_eq!(2, search_count("rust", contents, false));
+        assert_eq!(4, search_occurrences("rust", contents, false));
+    }
+
+    /// Group separator test
+    ///
+    /// Test if a custom separator, or a blank line when empty, is printed between context groups
+    #[test]
+    fn group_separator() {
+        let filename = temp_file("separator.txt", "hit\nmiss\nmiss\nmiss\nhit\n");
+        let search = |separator: &str| {
+            let config = Config::new(&args(&["grep_remake", "-A", "1", "--group-separator", separator, "hit", &filename])).unwrap();
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        };
+
+        assert_eq!(vec!["hit", "miss", "== next ==", "hit"], search("== next =="));
+        assert_eq!(vec!["hit", "miss", "", "hit"], search(""));
+    }
+
+    /// Reader search test
+    ///
+    /// Test if a reader is searched line by line with 1-based numbers, and its errors are returned
+    #[test]
+    fn search_reader_pairs() {
+        let reader = io::Cursor::new(b"first\nsecond MATCH\nthird\nmatch again\n".to_vec());
+        assert_eq!(
+            vec![(2, "second MATCH".to_string()), (4, "match again".to_string())],
+            search_reader("match", reader, false).unwrap()
+        );
+
+        let reader = io::Cursor::new(b"match\n\xff\n".to_vec());
+        assert_eq!(io::ErrorKind::InvalidData, search_reader("match", reader, true).unwrap_err().kind());
+    }
+
+    /// Per line maximum test
+    ///
+    /// Test if --max-per-line caps the matches printed from a line but not the -oc count
+    #[test]
+    fn max_per_line() {
+        let filename = temp_file("per_line.txt", "ab ab ab ab ab
+ab
+");
+
+        let config = Config::new(&args(&["grep_remake", "-on", "--max-per-line", "2", "ab", &filename])).unwrap();
+        assert_eq!(vec!["1:ab", "1:ab", "2:ab"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "-oc", "--max-per-line", "2", "ab", &filename])).unwrap();
+        assert_eq!(vec!["6"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Resume test
+    ///
+    /// Test if --resume-from searches from a byte offset, reports where it ended and numbers lines from --line-base
+    #[test]
+    fn resume_from_offset() {
+        let filename = temp_file("resume.txt", "match one\nmatch two\nmatch three\n");
+
+        let config = Config::new(&args(&["grep_remake", "-n", "--resume-from", "10", "match", &filename])).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        run(config, &mut out, &mut err).unwrap();
+        assert_eq!("1:match two\n2:match three\n", String::from_utf8(out).unwrap());
+        assert_eq!(format!("grep_remake: {}: end offset 32\n", filename), String::from_utf8(err).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "-n", "--resume-from", "10", "--line-base", "1", "match", &filename])).unwrap();
+        assert_eq!(vec!["2:match two", "3:match three"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Paragraph mode test
+    ///
+    /// Test if a match prints its whole paragraph and a blank line, including a last paragraph without one
+    #[test]
+    fn paragraph_mode() {
+        let filename = temp_file("paragraphs.txt", "[server]\nhost = a\nport = 80\n\n[client]\nretries = 3\n\n\n[cache]\nport = 90");
+        let config = Config::new(&args(&["grep_remake", "--paragraph", "port", &filename])).unwrap();
+
+        assert_eq!(
+            vec!["[server]", "host = a", "port = 80", "", "[cache]", "port = 90", ""],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+    }
+
+    /// Nth match test
+    ///
+    /// Test if --nth prints only the match at that position across files, failing when there are fewer
+    #[test]
+    fn nth_match() {
+        let first = temp_file("nth_first.txt", "match a\nskip\nmatch b\n");
+        let second = temp_file("nth_second.txt", "match c\nmatch d\n");
+
+        let config = Config::new(&args(&["grep_remake", "-h", "--nth", "3", "match", &first, &second])).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(0, run(config, &mut out, &mut io::sink()).unwrap());
+        assert_eq!("match c\n", String::from_utf8(out).unwrap());
+
+        let config = Config::new(&args(&["grep_remake", "--nth", "5", "match", &first, &second])).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(1, run(config, &mut out, &mut io::sink()).unwrap());
+        assert!(out.is_empty());
+    }
+
+    /// First only test
+    ///
+    /// Test if --first-only prints the path, line number and text of just the first match in each file
+    #[test]
+    fn first_match_per_file() {
+        let first = temp_file("first_only_a.txt", "skip\nmatch one\nmatch two\n");
+        let second = temp_file("first_only_b.txt", "match three\nmatch four\n");
+
+        let config = Config::new(&args(&["grep_remake", "--first-only", "match", &first, &second])).unwrap();
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{}:2:match one\n{}:1:match three\n", first, second), String::from_utf8(out).unwrap());
+    }
+
+    /// JSON output test
+    ///
+    /// Test if each JSON line holds the merged, ordered byte ranges of every pattern in the text
+    #[test]
+    fn json_output() {
+        let filename = temp_file("report.json.txt", "say \"hi\" and hi\nskip\n");
+        let config = Config::new(&args(&["grep_remake", "--format", "json", "-e", "hi", "-e", "and", &filename])).unwrap();
+
+        assert_eq!(
+            vec![r#"{"path":"FILE","line_number":1,"text":"say \"hi\" and hi","matches":[{"start":5,"end":7},{"start":9,"end":12},{"start":13,"end":15}]}"#
+                .replace("FILE", &filename)],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+    }
+
+    /// JSON array test
+    ///
+    /// Test if the array is valid with no, one and several elements, commas only between them
+    #[test]
+    fn json_array_output() {
+        let first = temp_file("array_a.txt", "one match
+skip
+");
+        let second = temp_file("array_b.txt", "skip
+match two
+match three
+");
+        let output = |query: &str, files: &[&str]| {
+            let mut arguments = vec!["grep_remake", "--format", "json-array", query];
+            arguments.extend(files);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let element = |path: &str, line_number: usize, text: &str| {
+            let start = text.find("match").unwrap();
+            let found: Vec<Range<usize>> = std::iter::once(start..start + 5).collect();
+            json_row(path, line_number, text, &found)
+        };
+
+        assert_eq!("[]\n", output("absent", &[&first, &second]));
+        assert_eq!(format!("[\n{}\n]\n", element(&first, 1, "one match")), output("match", &[&first]));
+        assert_eq!(
+            format!(
+                "[\n{},\n{},\n{}\n]\n",
+                element(&first, 1, "one match"),
+                element(&second, 2, "match two"),
+                element(&second, 3, "match three")
+            ),
+            output("match", &[&first, &second])
+        );
+    }
+
+    /// Empty pattern test
+    ///
+    /// Test if an empty pattern is rejected unless --allow-empty, which then matches every line
+    #[test]
+    fn empty_pattern() {
+        let filename = temp_file("empty_pattern.txt", "one\ntwo\n");
+
+        assert!(Config::new(&args(&["grep_remake", "", &filename])).is_err());
+        assert!(Config::new(&args(&["grep_remake", "-e", "one", "-e", "", &filename])).is_err());
+        assert!(Config::new(&args(&["grep_remake", "--alternatives", "|", &filename])).is_err());
+
+        let config = Config::new(&args(&["grep_remake", "--allow-empty", "", &filename])).unwrap();
+        assert_eq!(vec!["one", "two"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Column range test
+    ///
+    /// Test if a token only matches inside the given columns, the whole line being printed
+    #[test]
+    fn column_range() {
+        let filename = temp_file("columns.txt", "ERR  00042 ok\nOK   ERR00 ok\nOK   00042 ERR\nERR\n");
+        let config = Config::new(&args(&["grep_remake", "--columns", "6:10", "ERR", &filename])).unwrap();
+        assert_eq!(vec!["OK   ERR00 ok"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        let config = Config::new(&args(&["grep_remake", "--columns", "1:3", "ERR", &filename])).unwrap();
+        assert_eq!(vec!["ERR  00042 ok", "ERR"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+
+        assert!(Config::new(&args(&["grep_remake", "--columns", "5:2", "ERR", &filename])).is_err());
+    }
+
+    /// Trailing newline test
+    ///
+    /// Test if every line ends with one newline by default, and --no-trailing-newline keeps a missing one missing
+    #[test]
+    fn trailing_newline() {
+        let filename = temp_file("no_newline.txt", "match one\r\nskip\nmatch two");
+        let output = |options: &[&str]| {
+            let mut arguments = vec!["grep_remake"];
+            arguments.extend(options);
+            arguments.extend(["match", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("match one\nmatch two\n", output(&[]));
+        assert_eq!("match one\nmatch two", output(&["--no-trailing-newline"]));
+        assert!(output(&["--format", "csv", "--no-trailing-newline"]).ends_with(",3,match two\n"));
+    }
+
+    /// Blame test
+    ///
+    /// Test if matches in a committed file end with their author and commit, when git is installed
+    #[test]
+    fn blame_annotations() {
+        let git = |dir: &Path, arguments: &[&str]| {
+            Command::new("git").arg("-C").arg(dir).args(arguments).stdout(Stdio::null()).stderr(Stdio::null()).status()
+        };
+        let dir = env::temp_dir().join(format!("grep_remake_blame_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        if !git(&dir, &["init", "-q"]).is_ok_and(|status| status.success()) {
+            return;
+        }
+
+        fs::write(dir.join("code.rs"), "fn main() {}\nlet todo = 1;\n").unwrap();
+        git(&dir, &["add", "code.rs"]).unwrap();
+        git(&dir, &["-c", "user.name=Ada L", "-c", "user.email=ada@example.com", "commit", "-qm", "init"]).unwrap();
+        let filename = dir.join("code.rs").to_string_lossy().into_owned();
+
+        let config = Config::new(&args(&["grep_remake", "--blame", "todo", &filename])).unwrap();
+        let lines = search_file(&config, &Target::new(&filename), false).unwrap().lines;
+        assert_eq!(1, lines.len());
+        assert!(lines[0].starts_with("let todo = 1; (Ada L, "), "{}", lines[0]);
+
+        // An untracked file is printed as usual
+        let untracked = temp_file("untracked_blame.txt", "todo here\n");
+        assert_eq!(vec!["todo here"], search_file(&config, &Target::new(&untracked), false).unwrap().lines);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Relative time test
+    ///
+    /// Test if timestamped matches are prefixed with how long ago they were, and others left alone
+    #[test]
+    fn relative_time() {
+        let filename = temp_file("timestamped.log", "2001-09-09 01:46:40 error one\n[boot] error two\n09/09/2001 error three\n");
+
+        let config = Config::new(&args(&["grep_remake", "--relative-time", "error", &filename])).unwrap();
+        let lines = search_file(&config, &Target::new(&filename), false).unwrap().lines;
+        let ago = timestamp::relative(1_000_000_000, timestamp::now());
+        assert_eq!(format!("[{}] 2001-09-09 01:46:40 error one", ago), lines[0]);
+        assert_eq!(vec!["[boot] error two", "09/09/2001 error three"], lines[1..]);
+
+        let config = Config::new(&args(&["grep_remake", "--relative-time", "--timestamp-format", "%d/%m/%Y", "error", &filename])).unwrap();
+        let lines = search_file(&config, &Target::new(&filename), false).unwrap().lines;
+        assert!(lines[2].starts_with('[') && lines[2].ends_with(" ago] 09/09/2001 error three"), "{}", lines[2]);
+    }
+
+    /// Collapse test
+    ///
+    /// Test if runs of identical matching lines print once with their count, a different line ending the run
+    #[test]
+    fn collapse_repeats() {
+        let filename = temp_file("repeats.log", "retry\nretry\nretry\nretry failed\nretry\nretry\nok\nretry\n");
+        let config = Config::new(&args(&["grep_remake", "-n", "--collapse", "retry", &filename])).unwrap();
+
+        assert_eq!(
+            vec!["1:retry (repeated 3 times)", "4:retry failed", "5:retry (repeated 2 times)", "8:retry"],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+    }
+
+    /// Section test
+    ///
+    /// Test if each match is prefixed with the last header before it, skipping sections without matches
+    #[test]
+    fn section_headers() {
+        let filename = temp_file("sections.ini", "host = none\n[database]\nhost = db\nport = 5432\n[logging]\nlevel = info\n[cache]\nhost = c\nhost = d\n");
+        let config = Config::new(&args(&["grep_remake", "--section", "^[", "host", &filename])).unwrap();
+
+        assert_eq!(
+            vec!["host = none", "[database] host = db", "[cache] host = c", "[cache] host = d"],
+            search_file(&config, &Target::new(&filename), false).unwrap().lines
+        );
+
+        let config = Config::new(&args(&["grep_remake", "--section", "^[", "cache", &filename])).unwrap();
+        assert_eq!(vec!["[cache]"], search_file(&config, &Target::new(&filename), false).unwrap().lines);
+    }
+
+    /// Saved pattern test
+    ///
+    /// Test if a search loaded from a saved pattern matches the same lines as the search that saved it
+    #[test]
+    fn saved_pattern_search() {
+        let filename = temp_file("saved.log", "WARN low disk\nerror: net\ninfo\n");
+        let saved = env::temp_dir().join(format!("grep_remake_search_{}.pattern", std::process::id())).to_string_lossy().into_owned();
+
+        let config = Config::new(&args(&["grep_remake", "-i", "--alternatives", "--save-pattern", &saved, "warn|ERROR", &filename])).unwrap();
+        let mut first = Vec::new();
+        run(config, &mut first, &mut io::sink()).unwrap();
+
+        let config = Config::new(&args(&["grep_remake", "--load-pattern", &saved, &filename])).unwrap();
+        assert_eq!(vec!["warn", "ERROR"], config.patterns());
+        assert!(!config.case_sensitive);
+        let mut second = Vec::new();
+        run(config, &mut second, &mut io::sink()).unwrap();
+
+        assert_eq!("WARN low disk\nerror: net\n", String::from_utf8(first.clone()).unwrap());
+        assert_eq!(first, second);
+        fs::remove_file(&saved).unwrap();
+    }
+
+    /// Directory list test
+    ///
+    /// Test if -l --dirnames prints each directory with a matching file once, in the order first found
+    #[test]
+    fn list_dirnames() {
+        let first = temp_file("dirnames_a/one.txt", "match\n");
+        let second = temp_file("dirnames_b/two.txt", "match\n");
+        let third = temp_file("dirnames_a/three.txt", "match\n");
+        let fourth = temp_file("dirnames_c/four.txt", "nothing\n");
+        let config = Config::new(&args(&["grep_remake", "-l", "--dirnames", "match", &first, &second, &third, &fourth])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(format!("{}\n{}\n", parent_dir(&first), parent_dir(&second)), String::from_utf8(out).unwrap());
+        assert!(parent_dir(&first).ends_with("dirnames_a"));
+        assert_eq!(".", parent_dir("notes.txt"));
+    }
+
+    /// Error variant test
+    ///
+    /// Test if a missing saved pattern, a bad option and an empty pattern each give their own variant
+    #[test]
+    fn error_variants() {
+        let missing = env::temp_dir().join("grep_remake_no_such_file.txt").to_string_lossy().into_owned();
+        match Config::new(&args(&["grep_remake", "--load-pattern", &missing, "file"])) {
+            Err(error @ GrepError::Io { .. }) => {
+                assert!(error.to_string().starts_with(&format!("{}: ", missing)), "{}", error);
+                assert!(matches!(error, GrepError::Io { error, .. } if error.kind() == io::ErrorKind::NotFound));
+            }
+            other => panic!("expected an IO error, got {:?}", other.map(|_| ())),
+        }
+
+        assert!(matches!(Config::new(&args(&["grep_remake", "--no-such-option", "query", "file"])), Err(GrepError::Config(_))));
+        assert!(matches!(Config::new(&args(&["grep_remake", "", "file"])), Err(GrepError::InvalidPattern(_))));
+    }
+
+    /// Empty file test
+    ///
+    /// Test if an empty file prints nothing, counts 0 only with --with-zero and is listed by -L
+    #[test]
+    fn empty_file() {
+        let filename = temp_file("empty.txt", "");
+        let output = |options: &[&str]| {
+            let mut arguments = vec!["grep_remake"];
+            arguments.extend(options);
+            arguments.extend(["match", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("", output(&[]));
+        assert_eq!("", output(&["-c"]));
+        assert_eq!("0\n", output(&["-c", "--with-zero"]));
+        assert_eq!("", output(&["-l"]));
+        assert_eq!(format!("{}\n", filename), output(&["-L"]));
+    }
+
+    /// Dimmed context test
+    ///
+    /// Test if --color-context dim wraps context lines in the faint code and matched lines in bold
+    #[test]
+    fn dimmed_context() {
+        let filename = temp_file("dimmed.txt", "before\nthe hit\nafter\n");
+        let config = Config::new(&args(&["grep_remake", "--color", "--color-context", "dim", "-C", "1", "hit", &filename])).unwrap();
+
+        let mut out = Vec::new();
+        run(config, &mut out, &mut io::sink()).unwrap();
+        assert_eq!(
+            "\x1b[2mbefore\x1b[0m\n\x1b[1mthe \x1b[1;31mhit\x1b[0m\x1b[1m\x1b[0m\n\x1b[2mafter\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    /// Named pipe test
+    ///
+    /// Test if a FIFO is searched as a stream, including with --dedup-files and --resume-from, where mkfifo exists
+    #[cfg(unix)]
+    #[test]
+    fn named_pipe() {
+        let fifo = env::temp_dir().join(format!("grep_remake_fifo_{}", std::process::id()));
+        if !Command::new("mkfifo").arg(&fifo).status().is_ok_and(|status| status.success()) {
+            return;
+        }
+        let filename = fifo.to_string_lossy().into_owned();
+
+        let search = |options: &[&str]| {
+            // Opening a FIFO blocks until both ends are open, so the writer runs alongside
+            let writer_path = fifo.clone();
+            let writer = thread::spawn(move || fs::write(writer_path, "skip\npiped match\n").unwrap());
+            let mut arguments = vec!["grep_remake", "-j", "1"];
+            arguments.extend(options);
+            arguments.extend(["match", &filename]);
+            let mut out = Vec::new();
+            run(Config::new(&args(&arguments)).unwrap(), &mut out, &mut io::sink()).unwrap();
+            writer.join().unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!("piped match\n", search(&[]));
+        assert_eq!("piped match\n", search(&["--dedup-files"]));
+        assert_eq!("1:piped match\n", search(&["-n", "--resume-from", "5"]));
+        fs::remove_file(&fifo).unwrap();
+    }
+}
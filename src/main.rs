@@ -1,29 +1,117 @@
 use std::env;
+use std::io;
 use std::process;
 
 use grep_remake::Config;
 
+/// Text the self test searches, the same for every check
+const SELFTEST_CONTENTS: &str = "Rust:\nsafe, fast, productive.\nPick three.\nTrust me.\n";
+
 /// Main fuction of the program
 fn main() {
+    // Ctrl-C stops the search but still writes out what was found
+    grep_remake::signal::install();
+
     // Grabs arguments from environment
     let args: Vec<String> = env::args().collect();
 
+    // --selftest checks the library works here instead of searching anything
+    if args.len() == 2 && args[1] == "--selftest" {
+        process::exit(if selftest() { 0 } else { 1 });
+    }
+
+    // Defaults from a .grep_remakerc come first, so flags given here override them. The one in
+    // the current directory is only read when GREP_REMAKE_LOCAL_RC is set
+    let args = match grep_remake::rc::find() {
+        Some(path) => {
+            let defaults = grep_remake::rc::load(&path, &mut io::stderr()).unwrap_or_else(|e| {
+                eprintln!("grep_remake: {}: {}", path.display(), e);
+                Vec::new()
+            });
+            grep_remake::rc::with_defaults(&args, defaults)
+        }
+        None => args,
+    };
+
     // Creates Config struct for execution
-    let config = Config::new(&args)
+    let mut config = Config::new(&args)
         .unwrap_or_else(|err| {
             // Error handling -- if no arguments tell user in console
             eprintln!("Problem parsing arguments: {}", err);
-            process::exit(1);
+            process::exit(2);
         });
 
-    // Basic outputs
-    println!("Searching for {}", config.query);
-    println!("In file {}", config.filename);
-    
-    // Runs the grep with error check
-    if let Err(e) = grep_remake::run(config) {
-        // Error handling -- if anything goes wrong
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    // Lines are cut to fit the terminal unless --max-columns says otherwise, and never when piped
+    if config.max_columns.is_none() {
+        config.max_columns = grep_remake::terminal::width();
+    }
+
+    // Runs the grep with error check, writing to the console
+    match grep_remake::run(config, &mut io::stdout().lock(), &mut io::stderr().lock()) {
+        // Exits with the status the search asked for, like grep 0 for a match and 1 for none
+        Ok(status) => process::exit(status),
+        Err(e) => {
+            // Error handling -- if anything goes wrong, 2 as for grep's errors
+            eprintln!("Application error: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+/// Self test method
+///
+/// Runs each search mode over in-memory text and compares it with the known answer,
+/// printing a line per check, so a packaged binary can be checked without the test suite
+///
+/// # Returns
+///
+/// `bool` - True when every check passed
+fn selftest() -> bool {
+    // Options for the searches that take a Config, the filename is never opened
+    let config = |options: &[&str], query: &str| {
+        let mut args = vec!["grep_remake".to_string()];
+        args.extend(options.iter().map(|option| option.to_string()));
+        args.extend([query.to_string(), "selftest".to_string()]);
+        Config::new(&args).expect("self test arguments are valid")
+    };
+    let selected = |config: Config| -> Vec<String> {
+        grep_remake::search_config_reader(&config, SELFTEST_CONTENTS.as_bytes())
+            .map(|lines| lines.into_iter().map(|(_, line)| line).collect())
+            .unwrap_or_default()
+    };
+    // Modes that only print, such as -c, are run over a copy of the text in a temporary file
+    let printed = |options: &[&str], query: &str| -> String {
+        let path = std::env::temp_dir().join(format!("grep_remake-selftest-{}", process::id()));
+        if std::fs::write(&path, SELFTEST_CONTENTS).is_err() {
+            return String::new();
+        }
+        let mut args = vec!["grep_remake".to_string()];
+        args.extend(options.iter().map(|option| option.to_string()));
+        args.extend([query.to_string(), path.display().to_string()]);
+        let mut out = Vec::new();
+        let ran = Config::new(&args).map(|config| grep_remake::run(config, &mut out, &mut io::sink()));
+        let _ = std::fs::remove_file(&path);
+        match ran {
+            Ok(Ok(_)) => String::from_utf8_lossy(&out).into_owned(),
+            _ => String::new(),
+        }
+    };
+
+    let checks: Vec<(&str, bool)> = vec![
+        ("search", grep_remake::search("duct", SELFTEST_CONTENTS) == ["safe, fast, productive."]),
+        (
+            "case-insensitive",
+            grep_remake::search_case_insensitive("rUsT", SELFTEST_CONTENTS) == ["Rust:", "Trust me."],
+        ),
+        ("invert", selected(config(&["-v"], "st")) == ["Pick three."]),
+        ("count", printed(&["-c"], "e") == "3\n"),
+    ];
+
+    let failed = checks.iter().filter(|(_, passed)| !passed).count();
+    for (name, passed) in &checks {
+        println!("{} {}", if *passed { "ok  " } else { "FAIL" }, name);
     }
-}
\ No newline at end of file
+    println!("{} passed, {} failed", checks.len() - failed, failed);
+
+    failed == 0
+}
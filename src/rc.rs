@@ -0,0 +1,219 @@
+//! Default options read from a `.grep_remakerc` file
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{Config, GrepError};
+
+/// Name of the file defaults are read from
+pub const RC_FILENAME: &str = ".grep_remakerc";
+
+/// Environment variable that, when set, lets the current directory's defaults file be read
+pub const LOCAL_RC_VAR: &str = "GREP_REMAKE_LOCAL_RC";
+
+/// Find method
+///
+/// Looks for the defaults file in the home directory. The current directory is looked in
+/// first only when `LOCAL_RC_VAR` is set, as a file shipped in a cloned repository could
+/// otherwise run any command through `pre` just by being searched in.
+///
+/// # Returns
+///
+/// `Option<PathBuf>` - Path of the first one found
+pub fn find() -> Option<PathBuf> {
+    let local = env::var_os(LOCAL_RC_VAR).map(|_| PathBuf::from("."));
+    let home = env::var_os("HOME").map(PathBuf::from);
+    local
+        .into_iter()
+        .chain(home)
+        .map(|dir| dir.join(RC_FILENAME))
+        .find(|path| path.is_file())
+}
+
+/// Load method
+///
+/// Reads `key = value` lines into the options they stand for. A key is the name of a long
+/// option without its dashes: `true` turns a flag on, `false` leaves it off, and any other
+/// value is given to the option. A flag turned on here is turned back off on the command line
+/// by its `--no-` form, such as `--no-color`. `#` at the start of a line or after a space
+/// starts a comment, so values such as `^#` keep theirs. Settings that aren't valid options
+/// are reported on `err` and left out. One that is only valid alongside another, such as
+/// `in-place` with `replace`, is kept when the file has the other too.
+///
+/// # Parameters
+///
+/// `path` - The defaults file
+/// `err` - Where unknown settings are reported
+///
+/// # Returns
+///
+/// `io::Result<Vec<String>>` - The options, in the order they were written
+pub fn load(path: &Path, err: &mut dyn Write) -> io::Result<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+    // Each setting with its line number and, once checked alone, whether it was valid
+    let mut settings = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = without_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (line, "true"),
+        };
+        let setting = match value {
+            "true" => vec![format!("--{}", key)],
+            "false" => continue,
+            _ => vec![format!("--{}", key), value.to_string()],
+        };
+
+        // Each setting is checked on its own so one mistake doesn't lose the rest
+        let valid = check(&setting).is_ok();
+        settings.push((index + 1, key.to_string(), setting, valid));
+    }
+
+    // One that failed alone is checked again with the valid ones, which may be what it needs
+    let valid: Vec<String> = settings.iter().filter(|(.., valid)| *valid).flat_map(|(_, _, setting, _)| setting.clone()).collect();
+    let mut options = Vec::new();
+    for (number, key, setting, alone) in settings {
+        if alone {
+            options.extend(setting);
+            continue;
+        }
+        let together: Vec<String> = valid.iter().cloned().chain(setting.iter().cloned()).collect();
+        match check(&together) {
+            Ok(_) => options.extend(setting),
+            Err(e) => writeln!(err, "grep_remake: {}:{}: ignoring {}: {}", path.display(), number, key, e)?,
+        }
+    }
+
+    Ok(options)
+}
+
+/// Check method
+///
+/// # Parameters
+///
+/// `options` - Options from the defaults file
+///
+/// # Returns
+///
+/// `Result<Config, GrepError>` - Config for the options with a query and file, or the error
+/// they give
+fn check(options: &[String]) -> Result<Config, GrepError> {
+    let mut args = vec!["grep_remake".to_string()];
+    args.extend(options.iter().cloned());
+    args.extend(["query".to_string(), "file".to_string()]);
+    Config::new(&args)
+}
+
+/// Without comment method
+///
+/// # Parameters
+///
+/// `line` - A line of the defaults file
+///
+/// # Returns
+///
+/// `&str` - The line up to the first `#` that starts it or follows whitespace
+fn without_comment(line: &str) -> &str {
+    let comment = line
+        .char_indices()
+        .find(|&(index, c)| c == '#' && line[..index].chars().next_back().is_none_or(char::is_whitespace))
+        .map_or(line.len(), |(index, _)| index);
+    &line[..comment]
+}
+
+/// With defaults method
+///
+/// # Parameters
+///
+/// `args` - Arguments from the command line, the program first
+/// `defaults` - Options from the defaults file
+///
+/// # Returns
+///
+/// `Vec<String>` - The arguments with the defaults ahead of them, so a flag given on the
+/// command line is parsed later and takes precedence
+pub fn with_defaults(args: &[String], defaults: Vec<String>) -> Vec<String> {
+    let mut combined: Vec<String> = args.iter().take(1).cloned().collect();
+    combined.extend(defaults);
+    combined.extend(args.iter().skip(1).cloned());
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a list of owned arguments
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Defaults file test
+    ///
+    /// Test if settings become options that command line flags override, and unknown keys only warn
+    #[test]
+    fn loads_defaults() {
+        let path = env::temp_dir().join(format!("grep_remake_rc_{}", std::process::id()));
+        fs::write(&path, "# defaults\ncolor = true\nignore-case\nmax-per-file = 3\nno-such-key = 1\nstats = false\n").unwrap();
+
+        let mut err = Vec::new();
+        let defaults = load(&path, &mut err).unwrap();
+        assert_eq!(args(&["--color", "--ignore-case", "--max-per-file", "3"]), defaults);
+        assert!(String::from_utf8(err).unwrap().contains(":5: ignoring no-such-key"));
+
+        let config = Config::new(&with_defaults(&args(&["grep_remake", "query", "file"]), defaults.clone())).unwrap();
+        assert!(config.color);
+        assert!(!config.case_sensitive);
+        assert!(!config.stats);
+
+        let command_line = args(&["grep_remake", "--case-sensitive", "--max-per-file", "5", "query", "file"]);
+        let config = Config::new(&with_defaults(&command_line, defaults)).unwrap();
+        assert!(config.case_sensitive);
+        assert_eq!(Some(5), config.per_file_max);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Dependent settings test
+    ///
+    /// Test if a setting that needs another is kept when the file has both, and reported when it doesn't
+    #[test]
+    fn dependent_settings() {
+        let path = env::temp_dir().join(format!("grep_remake_rc_dependent_{}", std::process::id()));
+        fs::write(&path, "in-place = true\nreplace = X\n").unwrap();
+        let mut err = Vec::new();
+        assert_eq!(args(&["--in-place", "--replace", "X"]), load(&path, &mut err).unwrap());
+        assert!(err.is_empty());
+
+        fs::write(&path, "in-place = true\n").unwrap();
+        let mut err = Vec::new();
+        assert!(load(&path, &mut err).unwrap().is_empty());
+        assert!(String::from_utf8(err).unwrap().contains(":1: ignoring in-place: --in-place needs --replace"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Comment and negation test
+    ///
+    /// Test if only a `#` after whitespace starts a comment, and a flag from the file is turned off by its --no- form
+    #[test]
+    fn comments_and_negations() {
+        let path = env::temp_dir().join(format!("grep_remake_rc_negation_{}", std::process::id()));
+        fs::write(&path, "section = ^#  # headers\ncolor = true #on\n#stats\n").unwrap();
+
+        let defaults = load(&path, &mut io::sink()).unwrap();
+        assert_eq!(args(&["--section", "^#", "--color"]), defaults);
+
+        let config = Config::new(&with_defaults(&args(&["grep_remake", "query", "file"]), defaults.clone())).unwrap();
+        assert!(config.color);
+        let config = Config::new(&with_defaults(&args(&["grep_remake", "--no-color", "query", "file"]), defaults)).unwrap();
+        assert!(!config.color);
+        assert_eq!(Some("^#".to_string()), config.section_pattern);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! Saving the patterns of a search to a file and loading them again, for `--save-pattern`
+//! and `--load-pattern`
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::Config;
+
+/// First line of every saved pattern file, naming the version of the format
+const HEADER: &str = "grep_remake pattern 1";
+
+/// Saved pattern struct
+///
+/// Everything that decides which lines a search matches, so a search loaded from a file
+/// behaves exactly like the one that saved it
+///
+/// # Arguments
+///
+/// * `patterns` - The texts a line is searched for, already split into alternatives
+/// * `case_sensitive` - Whether the patterns are matched case sensitively
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SavedPattern {
+    pub patterns: Vec<String>,
+    pub case_sensitive: bool,
+}
+
+impl SavedPattern {
+    /// From config method
+    ///
+    /// # Parameters
+    ///
+    /// `config` - The given config of the execution
+    ///
+    /// # Returns
+    ///
+    /// `SavedPattern` - The config's patterns and flags
+    pub fn from_config(config: &Config) -> SavedPattern {
+        SavedPattern {
+            patterns: config.patterns().into_iter().map(String::from).collect(),
+            case_sensitive: config.case_sensitive,
+        }
+    }
+
+    /// Apply method
+    ///
+    /// Replaces the config's patterns and flags with these. The patterns become `-e` patterns,
+    /// as they are split into alternatives already.
+    ///
+    /// # Parameters
+    ///
+    /// `config` - The config to change
+    pub fn apply(self, config: &mut Config) {
+        config.query = self.patterns.first().cloned().unwrap_or_default();
+        config.expressions = self.patterns;
+        config.alternatives = None;
+        config.case_sensitive = self.case_sensitive;
+    }
+
+    /// Save method
+    ///
+    /// Writes the header, a `case-sensitive` line, then a `pattern` line for each pattern with
+    /// backslashes and line breaks escaped
+    ///
+    /// # Parameters
+    ///
+    /// `path` - The file to write
+    ///
+    /// # Returns
+    ///
+    /// `io::Result<()>` - Simple error flag
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut contents = format!("{}\ncase-sensitive {}\n", HEADER, self.case_sensitive);
+        for pattern in &self.patterns {
+            let escaped = pattern.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+            contents.push_str(&format!("pattern {}\n", escaped));
+        }
+        fs::write(path, contents)
+    }
+
+    /// Load method
+    ///
+    /// # Parameters
+    ///
+    /// `path` - A file written by `save`
+    ///
+    /// # Returns
+    ///
+    /// `io::Result<SavedPattern>` - The patterns and flags, or an `InvalidData` error for a file
+    /// that isn't a saved pattern
+    pub fn load(path: &Path) -> io::Result<SavedPattern> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message));
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("not a saved pattern"));
+        }
+
+        let mut saved = SavedPattern { patterns: Vec::new(), case_sensitive: true };
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("case-sensitive", value)) => {
+                    saved.case_sensitive = value.parse().map_err(|_| invalid("case-sensitive must be true or false"))?;
+                }
+                Some(("pattern", escaped)) => saved.patterns.push(unescape(escaped)),
+                _ => return Err(invalid("unrecognised line")),
+            }
+        }
+        Ok(saved)
+    }
+}
+
+/// Unescape method
+///
+/// # Parameters
+///
+/// `escaped` - A pattern as `save` writes it
+///
+/// # Returns
+///
+/// `String` - The pattern with its backslash escapes turned back into the characters
+fn unescape(escaped: &str) -> String {
+    let mut pattern = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            pattern.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => pattern.push('\n'),
+            Some('r') => pattern.push('\r'),
+            Some(other) => pattern.push(other),
+            None => pattern.push('\\'),
+        }
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Round trip test
+    ///
+    /// Test if a saved pattern loads back the same, escapes included, and other files are refused
+    #[test]
+    fn round_trip() {
+        let path = env::temp_dir().join(format!("grep_remake_saved_{}.pattern", std::process::id()));
+        let saved = SavedPattern { patterns: vec!["TODO".to_string(), "a\\nb\nc".to_string()], case_sensitive: false };
+
+        saved.save(&path).unwrap();
+        assert_eq!(saved, SavedPattern::load(&path).unwrap());
+
+        fs::write(&path, "TODO\n").unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, SavedPattern::load(&path).unwrap_err().kind());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+//! Answering searches over a Unix socket, for editors that keep one process running (`--serve`)
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::{display_text, find_patterns, json_row, json_string, search_stream, signal, Config};
+
+/// How long a client has to send its request before the connection is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the listener looks for Ctrl-C while no client is connecting
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// File cache type
+///
+/// Contents of the files searched so far by path, with the modification time they were read
+/// at so a changed file is read again
+type FileCache = HashMap<PathBuf, (SystemTime, Arc<Vec<u8>>)>;
+
+/// Serve method
+///
+/// Listens on a Unix socket until Ctrl-C, a stale socket left at the path being replaced.
+/// Anything else at the path is left alone and reported as an error. The socket is removed
+/// once the server stops.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution, whose options every request is searched with
+/// `path` - Where the socket is created
+///
+/// # Returns
+///
+/// `io::Result<()>` - Simple error flag, for a socket that couldn't be created
+pub fn serve(config: &Config, path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            let message = format!("{}: already exists and isn't a socket", path.display());
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, message));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let served = serve_listener(config, &UnixListener::bind(path)?);
+    let _ = fs::remove_file(path);
+    served
+}
+
+/// Serve listener method
+///
+/// Answers one request on each connection. A request is the query on one line and the path
+/// of the file to search on the next. The answer is a JSON object per matching line, as
+/// `--format json` writes them, or a single `{"error":"..."}` object, after which the
+/// connection is closed. Each connection is answered on its own thread, and one that sends
+/// nothing for `REQUEST_TIMEOUT` is dropped, so a slow client never holds up the others.
+/// The listener is polled rather than blocked on, as `accept` is retried after a signal, so
+/// Ctrl-C stops it within `ACCEPT_INTERVAL`.
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `listener` - The socket connections are accepted from
+///
+/// # Returns
+///
+/// `io::Result<()>` - Simple error flag, for a socket that stopped accepting connections
+pub fn serve_listener(config: &Config, listener: &UnixListener) -> io::Result<()> {
+    // Files stay cached across connections, which is the point of a long-running process
+    let cache = Mutex::new(FileCache::new());
+    listener.set_nonblocking(true)?;
+
+    thread::scope(|scope| {
+        while !signal::interrupted() {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_INTERVAL);
+                    continue;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            // Some platforms pass the listener's nonblocking mode on to the connection
+            stream.set_nonblocking(false)?;
+            let cache = &cache;
+            // A client that hangs up early or never finishes its request only loses its own answer
+            scope.spawn(move || {
+                let _ = stream.set_read_timeout(Some(REQUEST_TIMEOUT)).and_then(|_| handle(config, stream, cache));
+            });
+        }
+        Ok(())
+    })
+}
+
+/// Handle method
+///
+/// # Parameters
+///
+/// `config` - The given config of the execution
+/// `stream` - The connection to read a request from and answer
+/// `cache` - Contents of the files searched by earlier requests
+///
+/// # Returns
+///
+/// `io::Result<()>` - Simple error flag
+fn handle(config: &Config, stream: UnixStream, cache: &Mutex<FileCache>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut out = io::BufWriter::new(stream);
+
+    let mut query = String::new();
+    let mut path = String::new();
+    reader.read_line(&mut query)?;
+    reader.read_line(&mut path)?;
+    let query = query.trim_end_matches(['\r', '\n']);
+    let path = path.trim_end_matches(['\r', '\n']);
+
+    if query.is_empty() || path.is_empty() {
+        writeln!(out, "{{\"error\":{}}}", json_string("a request is a query line then a path line"))?;
+        return out.flush();
+    }
+
+    // The request's query takes the place of any patterns the server was started with
+    let request = Config {
+        query: query.to_string(),
+        expressions: Vec::new(),
+        alternatives: None,
+        filenames: vec![path.to_string()],
+        ..config.clone()
+    };
+
+    match cached_contents(cache, Path::new(path)).and_then(|contents| search_stream(&request, contents.as_slice())) {
+        Ok(scan) => {
+            for (line_number, line) in scan.matches {
+                let text = display_text(&request, line);
+                writeln!(out, "{}", json_row(path, line_number, &text, &find_patterns(&request, &text)))?;
+            }
+        }
+        Err(e) => writeln!(out, "{{\"error\":{}}}", json_string(&format!("{}: {}", path, e)))?,
+    }
+    out.flush()
+}
+
+/// Cached contents method
+///
+/// # Parameters
+///
+/// `cache` - Contents of the files read so far
+/// `path` - The file wanted
+///
+/// # Returns
+///
+/// `io::Result<Arc<Vec<u8>>>` - The file's contents, read again only if it changed since it
+/// was cached, and shared so the cache isn't locked while they are searched
+fn cached_contents(cache: &Mutex<FileCache>, path: &Path) -> io::Result<Arc<Vec<u8>>> {
+    let modified = fs::metadata(path)?.modified()?;
+    let mut cache = cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let stale = cache.get(path).is_none_or(|(cached, _)| *cached != modified);
+    if stale {
+        cache.insert(path.to_path_buf(), (modified, Arc::new(fs::read(path)?)));
+    }
+    Ok(Arc::clone(&cache[path].1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Read;
+
+    /// Socket request test
+    ///
+    /// Test if a request on the socket is answered with a JSON line per match, and a bad request with an error
+    #[test]
+    fn answers_requests() {
+        let dir = env::temp_dir().join(format!("grep_remake_serve_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("served.txt");
+        fs::write(&filename, "a match\nskip\nmatch again\n").unwrap();
+        let socket = dir.join("grep.sock");
+
+        let args: Vec<String> = ["grep_remake", "--serve", &socket.to_string_lossy()].iter().map(|arg| arg.to_string()).collect();
+        let config = Config::new(&args).unwrap();
+        let listener = UnixListener::bind(&socket).unwrap();
+        // The server runs until the test process exits
+        thread::spawn(move || serve_listener(&config, &listener));
+
+        let request = |body: &str| {
+            let mut stream = UnixStream::connect(&socket).unwrap();
+            stream.write_all(body.as_bytes()).unwrap();
+            // Closing the writing half ends a request that is missing its path line
+            stream.shutdown(std::net::Shutdown::Write).unwrap();
+            let mut answer = String::new();
+            stream.read_to_string(&mut answer).unwrap();
+            answer
+        };
+
+        let path = filename.to_string_lossy();
+        assert_eq!(
+            format!(
+                "{{\"path\":\"{0}\",\"line_number\":1,\"text\":\"a match\",\"matches\":[{{\"start\":2,\"end\":7}}]}}\n\
+                 {{\"path\":\"{0}\",\"line_number\":3,\"text\":\"match again\",\"matches\":[{{\"start\":0,\"end\":5}}]}}\n",
+                path
+            ),
+            request(&format!("match\n{}\n", path))
+        );
+        assert!(request("match\n").starts_with("{\"error\":"));
+
+        // A client that connects and sends nothing doesn't hold up the next one
+        let _silent = UnixStream::connect(&socket).unwrap();
+        assert!(request(&format!("again\n{}\n", path)).contains("\"line_number\":3"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Socket path test
+    ///
+    /// Test if a file that isn't a socket is left in place rather than replaced by the server's socket
+    #[test]
+    fn keeps_other_files() {
+        let dir = env::temp_dir().join(format!("grep_remake_serve_keep_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let kept = dir.join("keep.txt");
+        fs::write(&kept, "not a socket\n").unwrap();
+
+        let args: Vec<String> = ["grep_remake", "--serve", &kept.to_string_lossy()].iter().map(|arg| arg.to_string()).collect();
+        let config = Config::new(&args).unwrap();
+        assert_eq!(io::ErrorKind::AlreadyExists, serve(&config, &kept).unwrap_err().kind());
+        assert_eq!("not a socket\n", fs::read_to_string(&kept).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
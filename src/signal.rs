@@ -0,0 +1,60 @@
+//! Noticing Ctrl-C so a search can stop cleanly
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the signal handler once SIGINT has been received
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Exit status for a process stopped by SIGINT (128 + the signal number)
+pub const INTERRUPTED_STATUS: i32 = 130;
+
+/// Install method
+///
+/// Installs a SIGINT handler that only records the signal. Searches check for it between
+/// lines, then stop, flush what they have written and exit with `INTERRUPTED_STATUS`. The
+/// handler doesn't restart system calls, so a read blocked on a FIFO or an idle stdin fails
+/// with `ErrorKind::Interrupted` and the search stops there too. Does nothing on platforms
+/// without the signal.
+pub fn install() {
+    #[cfg(unix)]
+    unix::install();
+}
+
+/// Interrupted method
+///
+/// # Returns
+///
+/// `bool` - True once SIGINT has been received
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::os::raw::c_int;
+    use std::sync::atomic::Ordering;
+
+    use super::INTERRUPTED;
+
+    /// SIGINT has the same number on every Unix
+    const SIGINT: c_int = 2;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        fn siginterrupt(signum: c_int, flag: c_int) -> c_int;
+    }
+
+    /// Signal handler, which may only do async-signal-safe work such as an atomic store
+    extern "C" fn on_interrupt(_: c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    pub fn install() {
+        // SAFETY: the handler only performs an atomic store
+        unsafe {
+            signal(SIGINT, on_interrupt);
+            // signal() asks for SA_RESTART, which would leave a blocking read waiting after Ctrl-C
+            siginterrupt(SIGINT, 1);
+        }
+    }
+}
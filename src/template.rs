@@ -0,0 +1,169 @@
+//! Output templates such as `{path}:{line}:{col}: {text}`
+
+use std::ops::Range;
+
+/// Field enum
+///
+/// A value a template placeholder is replaced with
+///
+/// # Variants
+///
+/// * `Path` - `{path}`, the file (or archive member) the match is in
+/// * `Line` - `{line}`, the line number, starting at 1
+/// * `Column` - `{col}`, the byte column the match starts at, starting at 1
+/// * `Text` - `{text}`, the whole matching line
+/// * `Match` - `{match}`, just the matched text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Path,
+    Line,
+    Column,
+    Text,
+    Match,
+}
+
+/// Piece enum
+///
+/// # Variants
+///
+/// * `Literal` - Text copied to the output as it is
+/// * `Field` - A placeholder
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Piece {
+    Literal(String),
+    Field(Field),
+}
+
+/// Template struct
+///
+/// A parsed `--format-template`, expanded once for every match
+///
+/// # Arguments
+///
+/// * `pieces` - The literal text and placeholders, in order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    pub pieces: Vec<Piece>,
+}
+
+impl Template {
+    /// Parse method
+    ///
+    /// Literal braces are written doubled, `{{` and `}}`
+    ///
+    /// # Parameters
+    ///
+    /// `source` - The template as given on the command line
+    ///
+    /// # Returns
+    ///
+    /// `Result<Template, &str>` - The template, or an error for an unknown or unclosed placeholder
+    pub fn parse(source: &str) -> Result<Template, &'static str> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err("Unclosed placeholder in format template"),
+                        }
+                    }
+
+                    let field = match name.as_str() {
+                        "path" => Field::Path,
+                        "line" => Field::Line,
+                        "col" => Field::Column,
+                        "text" => Field::Text,
+                        "match" => Field::Match,
+                        _ => return Err("Unknown placeholder in format template"),
+                    };
+
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err("Unmatched } in format template, write }} for a literal brace"),
+                _ => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+        Ok(Template { pieces })
+    }
+
+    /// Expand method
+    ///
+    /// # Parameters
+    ///
+    /// `path` - Name of the file the match is in
+    /// `line_number` - Position of the line in its file, starting at 1
+    /// `line` - Text of the matching line
+    /// `found` - Byte range of the match within the line
+    ///
+    /// # Returns
+    ///
+    /// `String` - The template with each placeholder replaced
+    pub fn expand(&self, path: &str, line_number: usize, line: &str, found: Range<usize>) -> String {
+        let mut output = String::new();
+
+        for piece in &self.pieces {
+            match piece {
+                Piece::Literal(text) => output.push_str(text),
+                Piece::Field(Field::Path) => output.push_str(path),
+                Piece::Field(Field::Line) => output.push_str(&line_number.to_string()),
+                Piece::Field(Field::Column) => output.push_str(&(found.start + 1).to_string()),
+                Piece::Field(Field::Text) => output.push_str(line),
+                Piece::Field(Field::Match) => output.push_str(&line[found.clone()]),
+            }
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Template expansion test
+    ///
+    /// Test if every placeholder is filled in from the match
+    #[test]
+    fn expands_placeholders() {
+        let template = Template::parse("{path}:{line}:{col}: {text}").unwrap();
+        assert_eq!("a.rs:3:5: let x = 1;", template.expand("a.rs", 3, "let x = 1;", 4..5));
+
+        let template = Template::parse("[{match}] at {line}").unwrap();
+        assert_eq!("[x] at 3", template.expand("a.rs", 3, "let x = 1;", 4..5));
+    }
+
+    /// Template escape test
+    ///
+    /// Test if doubled braces are literal and unknown or unclosed placeholders are rejected
+    #[test]
+    fn escapes_and_errors() {
+        let template = Template::parse("{{{match}}} }}{{").unwrap();
+        assert_eq!("{x} }{", template.expand("a.rs", 1, "x", 0..1));
+
+        assert!(Template::parse("{file}").is_err());
+        assert!(Template::parse("{path").is_err());
+        assert!(Template::parse("text}").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! Finding the width of the terminal output is written to, for automatic `--max-columns`
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// Width method
+///
+/// Asks the terminal on stdout for its size, falling back to the `COLUMNS` environment
+/// variable where that can't be done
+///
+/// # Returns
+///
+/// `Option<usize>` - Number of columns, or None when stdout isn't a terminal (such as when
+/// piped) and output shouldn't be cut to fit one
+pub fn width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+
+    #[cfg(unix)]
+    if let Some(columns) = unix::stdout_columns() {
+        return Some(columns);
+    }
+    env::var("COLUMNS").ok()?.parse().ok().filter(|columns| *columns > 0)
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::os::raw::{c_int, c_ulong};
+
+    /// Request for the window size, which differs between kernels
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    /// File descriptor of stdout
+    const STDOUT: c_int = 1;
+
+    /// The `struct winsize` filled in by `TIOCGWINSZ`
+    #[repr(C)]
+    #[derive(Default)]
+    struct WinSize {
+        rows: u16,
+        columns: u16,
+        x_pixels: u16,
+        y_pixels: u16,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    pub fn stdout_columns() -> Option<usize> {
+        let mut size = WinSize::default();
+        // SAFETY: TIOCGWINSZ only writes a struct winsize through the pointer it is given
+        let status = unsafe { ioctl(STDOUT, TIOCGWINSZ, &mut size as *mut WinSize) };
+        // Some terminals, such as serial consoles, report a size of zero
+        (status == 0 && size.columns > 0).then_some(usize::from(size.columns))
+    }
+}
@@ -0,0 +1,145 @@
+//! Reading the timestamp at the start of a log line, for `--relative-time`
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Format used by `--relative-time` without `--timestamp-format`
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parse prefix method
+///
+/// Reads a timestamp from the start of a line. The format is strptime-like: `%Y` (four
+/// digit year), `%m`, `%d`, `%H`, `%M` and `%S` (two digits each) and `%%` for a percent
+/// sign, anything else having to appear as it is. Times are taken to be UTC.
+///
+/// # Parameters
+///
+/// `format` - The format of the timestamp
+/// `line` - Text of the line
+///
+/// # Returns
+///
+/// `Option<i64>` - Seconds since the Unix epoch, or None if the line doesn't start with such a timestamp
+pub fn parse_prefix(format: &str, line: &str) -> Option<i64> {
+    let (mut year, mut month, mut day, mut hour, mut minute, mut second) = (1970, 1, 1, 0, 0, 0);
+    let mut rest = line.as_bytes();
+    let mut specs = format.chars();
+
+    while let Some(c) = specs.next() {
+        if c != '%' {
+            // Literal text must match exactly
+            let mut encoded = [0; 4];
+            rest = rest.strip_prefix(c.encode_utf8(&mut encoded).as_bytes())?;
+            continue;
+        }
+
+        let (field, width) = match specs.next()? {
+            '%' => {
+                rest = rest.strip_prefix(b"%")?;
+                continue;
+            }
+            'Y' => (&mut year, 4),
+            'm' => (&mut month, 2),
+            'd' => (&mut day, 2),
+            'H' => (&mut hour, 2),
+            'M' => (&mut minute, 2),
+            'S' => (&mut second, 2),
+            _ => return None,
+        };
+
+        let digits = rest.get(..width)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        *field = digits.iter().fold(0, |value, digit| value * 10 + i64::from(digit - b'0'));
+        rest = &rest[width..];
+    }
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Days from civil method
+///
+/// # Parameters
+///
+/// `year`, `month`, `day` - A date in the proleptic Gregorian calendar
+///
+/// # Returns
+///
+/// `i64` - Number of days from 1970-01-01 to the date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Counting years from March puts the leap day at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Now method
+///
+/// # Returns
+///
+/// `i64` - The current time in seconds since the Unix epoch
+pub fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Relative method
+///
+/// # Parameters
+///
+/// `then` - A time in seconds since the Unix epoch
+/// `now` - The current time, in the same units
+///
+/// # Returns
+///
+/// `String` - How long ago `then` was in its largest whole unit, such as `3h ago`, or how
+/// far off it is for a time in the future, such as `in 5m`
+pub fn relative(then: i64, now: i64) -> String {
+    let elapsed = (now - then).abs();
+    let amount = match elapsed {
+        0..=59 => format!("{}s", elapsed),
+        60..=3599 => format!("{}m", elapsed / 60),
+        3600..=86399 => format!("{}h", elapsed / 3600),
+        _ => format!("{}d", elapsed / 86400),
+    };
+
+    if then > now {
+        format!("in {}", amount)
+    } else {
+        format!("{} ago", amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Timestamp parse test
+    ///
+    /// Test if leading timestamps in a few formats are read as UTC, and other lines aren't
+    #[test]
+    fn parses_leading_timestamps() {
+        assert_eq!(Some(0), parse_prefix(DEFAULT_FORMAT, "1970-01-01 00:00:00 boot"));
+        assert_eq!(Some(951_782_400), parse_prefix(DEFAULT_FORMAT, "2000-02-29 00:00:00 leap day"));
+        assert_eq!(Some(1_700_000_000), parse_prefix("[%d/%m/%Y:%H:%M:%S]", "[14/11/2023:22:13:20] GET /"));
+        assert_eq!(None, parse_prefix(DEFAULT_FORMAT, "no timestamp here"));
+        assert_eq!(None, parse_prefix(DEFAULT_FORMAT, "2023-13-01 00:00:00 bad month"));
+    }
+
+    /// Relative time test
+    ///
+    /// Test if the difference is given in its largest whole unit
+    #[test]
+    fn describes_differences() {
+        assert_eq!("42s ago", relative(958, 1000));
+        assert_eq!("2m ago", relative(0, 150));
+        assert_eq!("3h ago", relative(0, 3 * 3600 + 5));
+        assert_eq!("10d ago", relative(0, 10 * 86400));
+        assert_eq!("in 5m", relative(300, 0));
+    }
+}
@@ -0,0 +1,91 @@
+//! Normalising each line before it is matched, for `--transform`
+
+use std::borrow::Cow;
+
+/// Transform enum
+///
+/// A change made to a line before the patterns are looked for in it. The line is still
+/// printed as it was.
+///
+/// # Variants
+///
+/// * `Lowercase` - Lower case every letter, like `-i` but for this search only
+/// * `Trim` - Remove whitespace from both ends
+/// * `Basename` - Keep only what follows the last `/`, for lines that are paths
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transform {
+    Lowercase,
+    Trim,
+    Basename,
+}
+
+impl Transform {
+    /// Parse method
+    ///
+    /// # Parameters
+    ///
+    /// `name` - Name of the transform, as given to `--transform`
+    ///
+    /// # Returns
+    ///
+    /// `Option<Transform>` - The transform, or None if there is none by that name
+    pub fn parse(name: &str) -> Option<Transform> {
+        match name {
+            "lowercase" => Some(Transform::Lowercase),
+            "trim" => Some(Transform::Trim),
+            "basename" => Some(Transform::Basename),
+            _ => None,
+        }
+    }
+
+    /// Apply method
+    ///
+    /// # Parameters
+    ///
+    /// `text` - Text of the line
+    ///
+    /// # Returns
+    ///
+    /// `Cow<str>` - The transformed text, borrowed when nothing had to be lower cased
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            Transform::Lowercase => Cow::Owned(text.to_lowercase()),
+            Transform::Trim => Cow::Borrowed(text.trim()),
+            Transform::Basename => Cow::Borrowed(text.rsplit('/').next().unwrap_or(text)),
+        }
+    }
+}
+
+/// Apply all method
+///
+/// # Parameters
+///
+/// `transforms` - The transforms, in the order they are applied
+/// `text` - Text of the line
+///
+/// # Returns
+///
+/// `Cow<str>` - The text after every transform
+pub fn apply_all<'a>(transforms: &[Transform], text: Cow<'a, str>) -> Cow<'a, str> {
+    transforms.iter().fold(text, |text, transform| match text {
+        Cow::Borrowed(text) => transform.apply(text),
+        Cow::Owned(text) => Cow::Owned(transform.apply(&text).into_owned()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transform order test
+    ///
+    /// Test if transforms are applied in the order given, each to the output of the last
+    #[test]
+    fn applies_in_order() {
+        let transforms = [Transform::Trim, Transform::Basename, Transform::Lowercase];
+        assert_eq!("main.rs", apply_all(&transforms, Cow::Borrowed("  src/bin/MAIN.rs  ")));
+        assert_eq!("src/a.rs", apply_all(&[Transform::Trim], Cow::Borrowed("\tsrc/a.rs ")));
+        // Taking the basename first leaves the trailing whitespace for trim to remove
+        assert_eq!("b", apply_all(&[Transform::Basename, Transform::Trim], Cow::Borrowed("a/ b ")));
+    }
+}
@@ -0,0 +1,257 @@
+use std::process::{Command, Output};
+
+/// Runs the built binary from inside the fixtures directory
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_grep-remake"))
+        .args(args)
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))
+        .output()
+        .expect("failed to run grep-remake")
+}
+
+/// Runs the built binary, expecting success, and returns its stdout
+fn grep_remake(args: &[&str]) -> Vec<u8> {
+    let output = run(args);
+
+    assert!(output.status.success(), "grep-remake failed: {}", String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+/// Recursive -l -Z test
+///
+/// Test if every matching filename is followed by a NUL, including the last, with nothing else written
+#[test]
+fn recursive_files_with_matches_null() {
+    assert_eq!(
+        b"a.txt\0sub/c.txt\0".to_vec(),
+        grep_remake(&["-l", "-r", "-Z", "needle", "tree"])
+    );
+}
+
+/// Recursive -l test
+///
+/// Test if filenames are newline terminated without -Z and each is listed once
+#[test]
+fn recursive_files_with_matches() {
+    assert_eq!(
+        b"a.txt\nsub/c.txt\n".to_vec(),
+        grep_remake(&["-l", "-r", "needle", "tree"])
+    );
+}
+
+/// Directory without -r test
+///
+/// Test if a directory argument is reported and skipped, failing with status 2 when it was the only target
+#[test]
+fn directory_without_recursion() {
+    let output = run(&["--no-recursive", "needle", "tree"]);
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!(b"grep_remake: tree: Is a directory\n".to_vec(), output.stderr);
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["needle", "tree", "tree/a.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(b"grep_remake: tree: Is a directory\n".to_vec(), output.stderr);
+    assert_eq!(b"tree/a.txt:the needle is here\n".to_vec(), output.stdout);
+}
+
+/// Exit status test
+///
+/// Test if the status is 0 with a match, 1 without one and 2 for an error, as grep's is
+#[test]
+fn exit_status() {
+    assert_eq!(Some(0), run(&["needle", "tree/a.txt"]).status.code());
+
+    let output = run(&["zzz", "tree/a.txt"]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+
+    assert_eq!(Some(2), run(&["needle", "no_such_file.txt"]).status.code());
+    assert_eq!(Some(2), run(&["--max-line-bytes", "0", "needle", "tree/a.txt"]).status.code());
+}
+
+/// Sole directory test
+///
+/// Test if a lone directory argument is searched recursively without -r
+#[test]
+fn sole_directory_recurses() {
+    assert_eq!(
+        b"a.txt:the needle is here\nsub/c.txt:another needle\nsub/c.txt:and a second needle\n".to_vec(),
+        grep_remake(&["needle", "tree"])
+    );
+}
+
+/// Multiple roots test
+///
+/// Test if files found under each directory are shown relative to it, and files given directly as given
+#[test]
+fn paths_relative_to_each_root() {
+    assert_eq!(
+        b"a.txt\nsub/c.txt\ntodo.txt\ntree/a.txt\n".to_vec(),
+        grep_remake(&["-l", "-r", "needle", "tree", "notes/", "tree/a.txt"])
+    );
+}
+
+/// Interrupt test
+///
+/// Test if Ctrl-C stops a search that is blocked waiting for input, still writing the buffered matches, with status 130
+#[cfg(unix)]
+#[test]
+fn interrupt_flushes_output() {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::process::Stdio;
+    use std::thread;
+    use std::time::Duration;
+
+    let fifo = std::env::temp_dir().join(format!("grep_remake_interrupt_{}", std::process::id()));
+    let _ = fs::remove_file(&fifo);
+    assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+    let child = Command::new(env!("CARGO_BIN_EXE_grep-remake"))
+        .arg("needle")
+        .arg(&fifo)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Opening blocks until the search has the pipe open for reading
+    let mut pipe = OpenOptions::new().write(true).open(&fifo).unwrap();
+    pipe.write_all(b"a needle\nnothing\n").unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    assert!(Command::new("kill").arg("-INT").arg(child.id().to_string()).status().unwrap().success());
+
+    // The pipe stays open, so the search only ends if Ctrl-C broke its blocked read
+    let mut child = child;
+    let mut waited = 0;
+    while child.try_wait().unwrap().is_none() && waited < 50 {
+        thread::sleep(Duration::from_millis(100));
+        waited += 1;
+    }
+    drop(pipe);
+
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&fifo).unwrap();
+
+    assert!(waited < 50, "the search kept waiting for input after Ctrl-C");
+    assert_eq!(Some(130), output.status.code());
+    assert_eq!(b"a needle\n".to_vec(), output.stdout);
+}
+
+/// Server interrupt test
+///
+/// Test if Ctrl-C stops a server waiting for connections, removing its socket, with status 130
+#[cfg(unix)]
+#[test]
+fn interrupt_stops_server() {
+    use std::fs;
+    use std::thread;
+    use std::time::Duration;
+
+    let socket = std::env::temp_dir().join(format!("grep_remake_serve_interrupt_{}", std::process::id()));
+    let _ = fs::remove_file(&socket);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_grep-remake")).arg("--serve").arg(&socket).spawn().unwrap();
+
+    let mut waited = 0;
+    while !socket.exists() && waited < 50 {
+        thread::sleep(Duration::from_millis(100));
+        waited += 1;
+    }
+    assert!(Command::new("kill").arg("-INT").arg(child.id().to_string()).status().unwrap().success());
+
+    let mut waited = 0;
+    while child.try_wait().unwrap().is_none() && waited < 50 {
+        thread::sleep(Duration::from_millis(100));
+        waited += 1;
+    }
+    if waited == 50 {
+        child.kill().unwrap();
+    }
+
+    assert_eq!(Some(130), child.wait().unwrap().code());
+    assert!(!socket.exists());
+}
+
+/// Local defaults test
+///
+/// Test if a defaults file in the current directory is only read when GREP_REMAKE_LOCAL_RC is set
+#[test]
+fn local_rc_needs_opt_in() {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("grep_remake_local_rc_{}", std::process::id()));
+    fs::create_dir_all(dir.join("home")).unwrap();
+    fs::write(dir.join(".grep_remakerc"), "invert-match\n").unwrap();
+    fs::write(dir.join("a.txt"), "hi\nbye\n").unwrap();
+
+    let search = |opt_in: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_grep-remake"));
+        command.args(["hi", "a.txt"]).current_dir(&dir).env("HOME", dir.join("home"));
+        if opt_in {
+            command.env("GREP_REMAKE_LOCAL_RC", "1");
+        } else {
+            command.env_remove("GREP_REMAKE_LOCAL_RC");
+        }
+        command.output().unwrap().stdout
+    };
+    assert_eq!(b"hi\n".to_vec(), search(false));
+    assert_eq!(b"bye\n".to_vec(), search(true));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Self test
+///
+/// Test if --selftest runs its checks and passes
+#[test]
+fn selftest_passes() {
+    let stdout = String::from_utf8(grep_remake(&["--selftest"])).unwrap();
+    assert!(stdout.contains("ok   invert\n"));
+    assert!(stdout.ends_with("4 passed, 0 failed\n"));
+}
+
+/// Decompress test
+///
+/// Test if bzip2 and xz files are searched decompressed, and one that won't decompress is skipped with a warning
+#[test]
+fn decompress_bzip2_and_xz() {
+    assert_eq!(
+        b"compressed/notes.txt.bz2:the needle is compressed\ncompressed/notes.txt.xz:the needle is compressed\n".to_vec(),
+        grep_remake(&["--decompress", "needle", "compressed/notes.txt.bz2", "compressed/notes.txt.xz"])
+    );
+
+    let output = run(&["--decompress", "needle", "compressed/broken.txt.xz", "compressed/notes.txt.xz"]);
+    assert_eq!(b"grep_remake: compressed/broken.txt.xz: xz could not decompress it\n".to_vec(), output.stderr);
+    assert_eq!(b"compressed/notes.txt.xz:the needle is compressed\n".to_vec(), output.stdout);
+}
+
+/// Absolute paths test
+///
+/// Test if --absolute-paths prints every file by its canonical path, in -l and line output
+#[test]
+fn absolute_paths() {
+    let tree = std::fs::canonicalize(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tree")).unwrap();
+    let a = tree.join("a.txt").to_string_lossy().into_owned();
+    let c = tree.join("sub/c.txt").to_string_lossy().into_owned();
+
+    assert_eq!(
+        format!("{}\n{}\n", a, c).into_bytes(),
+        grep_remake(&["--absolute-paths", "-l", "-r", "needle", "tree"])
+    );
+    assert_eq!(
+        format!("{}:the needle is here\n", a).into_bytes(),
+        grep_remake(&["--absolute-paths", "-H", "needle", "tree/a.txt"])
+    );
+}
+
+/// Thread count test
+///
+/// Test if searching on one thread, four threads and one per CPU all print the same output
+#[test]
+fn same_output_on_any_thread_count() {
+    let sequential = grep_remake(&["-j", "1", "-n", "-r", "e", "tree", "notes"]);
+    assert!(!sequential.is_empty());
+    assert_eq!(sequential, grep_remake(&["-j", "4", "-n", "-r", "e", "tree", "notes"]));
+    assert_eq!(sequential, grep_remake(&["-n", "-r", "e", "tree", "notes"]));
+}